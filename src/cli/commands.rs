@@ -44,6 +44,10 @@ pub enum Commands {
         #[arg(short, long)]
         drafts: bool,
 
+        /// Include posts dated in the future
+        #[arg(long)]
+        future: bool,
+
         /// Force full rebuild (ignore cache)
         #[arg(short, long)]
         force: bool,
@@ -63,11 +67,29 @@ pub enum Commands {
         #[arg(short, long)]
         drafts: bool,
 
+        /// Include posts dated in the future
+        #[arg(long)]
+        future: bool,
+
         /// Open browser automatically
         #[arg(short, long)]
         open: bool,
     },
 
+    /// Generate the stylesheet for class-based syntax highlighting
+    SyntaxCss {
+        /// Syntax theme name, bundled or from the site's syntaxes/ directory
+        theme: String,
+
+        /// Site root directory
+        #[arg(short, long, default_value = ".")]
+        root: PathBuf,
+
+        /// Write to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
     /// Clean build artifacts
     Clean {
         /// Site root directory
@@ -3,3 +3,4 @@ pub mod clean;
 pub mod commands;
 pub mod new;
 pub mod serve;
+pub mod syntax;
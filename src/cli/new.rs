@@ -153,6 +153,7 @@ toc_title: "Table of Contents"
 fn write_default_theme(site_dir: &Path) -> ForgeResult<()> {
     let theme_dir = site_dir.join("themes/default");
     fs::create_dir_all(theme_dir.join("templates/partials"))?;
+    fs::create_dir_all(theme_dir.join("templates/shortcodes"))?;
     fs::create_dir_all(theme_dir.join("static/css"))?;
     fs::create_dir_all(theme_dir.join("static/js"))?;
 
@@ -182,6 +183,10 @@ fn write_default_theme(site_dir: &Path) -> ForgeResult<()> {
             "templates/archive.html",
             include_str!("../../themes/default/templates/archive.html"),
         ),
+        (
+            "templates/section.html",
+            include_str!("../../themes/default/templates/section.html"),
+        ),
         (
             "templates/taxonomy.html",
             include_str!("../../themes/default/templates/taxonomy.html"),
@@ -210,6 +215,26 @@ fn write_default_theme(site_dir: &Path) -> ForgeResult<()> {
             "templates/partials/pagination.html",
             include_str!("../../themes/default/templates/partials/pagination.html"),
         ),
+        (
+            "templates/partials/toc.html",
+            include_str!("../../themes/default/templates/partials/toc.html"),
+        ),
+        (
+            "templates/partials/admonition.html",
+            include_str!("../../themes/default/templates/partials/admonition.html"),
+        ),
+        (
+            "templates/shortcodes/youtube.html",
+            include_str!("../../themes/default/templates/shortcodes/youtube.html"),
+        ),
+        (
+            "templates/shortcodes/figure.html",
+            include_str!("../../themes/default/templates/shortcodes/figure.html"),
+        ),
+        (
+            "templates/shortcodes/note.html",
+            include_str!("../../themes/default/templates/shortcodes/note.html"),
+        ),
         (
             "static/css/style.css",
             include_str!("../../themes/default/static/css/style.css"),
@@ -222,6 +247,10 @@ fn write_default_theme(site_dir: &Path) -> ForgeResult<()> {
             "static/js/search.js",
             include_str!("../../themes/default/static/js/search.js"),
         ),
+        (
+            "static/js/code.js",
+            include_str!("../../themes/default/static/js/code.js"),
+        ),
     ];
 
     for (path, content) in files {
@@ -4,11 +4,20 @@ use crate::config;
 use crate::error::ForgeResult;
 use crate::server::http;
 
-pub async fn serve_site(root: &Path, port: u16, drafts: bool, open: bool) -> ForgeResult<()> {
+pub async fn serve_site(
+    root: &Path,
+    port: u16,
+    drafts: bool,
+    future: bool,
+    open: bool,
+) -> ForgeResult<()> {
     let mut config = config::load_config(root)?;
     if drafts {
         config.build.include_drafts = true;
     }
+    if future {
+        config.build.include_future = true;
+    }
 
     http::start_server(root.to_path_buf(), config, port, open).await
 }
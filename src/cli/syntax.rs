@@ -0,0 +1,21 @@
+use std::fs;
+use std::path::Path;
+
+use crate::config::types::HighlightStyle;
+use crate::content::highlight::Highlighter;
+use crate::error::ForgeResult;
+
+pub fn syntax_css(root: &Path, theme: &str, output: Option<&Path>) -> ForgeResult<()> {
+    let highlighter = Highlighter::load(root, theme, HighlightStyle::Classes)?;
+    let css = highlighter.css(theme)?;
+
+    match output {
+        Some(path) => {
+            fs::write(path, &css)?;
+            println!("Wrote {}", path.display());
+        }
+        None => print!("{css}"),
+    }
+
+    Ok(())
+}
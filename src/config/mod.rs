@@ -27,5 +27,81 @@ fn validate_config(config: &SiteConfig) -> ForgeResult<()> {
             "posts_per_page must be greater than 0".to_string(),
         ));
     }
+    let (min, max) = (config.build.toc_min_level, config.build.toc_max_level);
+    if !(1..=6).contains(&min) || !(1..=6).contains(&max) || min > max {
+        return Err(ForgeError::Config(format!(
+            "toc_min_level and toc_max_level must be heading levels 1-6 with min <= max, got {min} and {max}"
+        )));
+    }
+    if !(1..=100).contains(&config.images.quality) {
+        return Err(ForgeError::Config(
+            "images.quality must be between 1 and 100".to_string(),
+        ));
+    }
+    if config.images.widths.contains(&0) {
+        return Err(ForgeError::Config(
+            "images.widths must be greater than 0".to_string(),
+        ));
+    }
+    for admonition in &config.admonitions {
+        let name = &admonition.name;
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(ForgeError::Config(format!(
+                "admonition names may only contain letters, digits, '-' and '_', got \"{name}\""
+            )));
+        }
+    }
+    for (lang, processor) in &config.code_processors {
+        if processor.command.is_empty() == processor.builtin.is_none() {
+            return Err(ForgeError::Config(format!(
+                "code_processors.{lang} needs either a command or a builtin, not both"
+            )));
+        }
+    }
+    let external_links = &config.external_links;
+    for value in &external_links.rel {
+        if value.is_empty() || value.contains(|c: char| c.is_whitespace() || c == '"') {
+            return Err(ForgeError::Config(format!(
+                "external_links.rel values must be single words, got \"{value}\""
+            )));
+        }
+    }
+    for host in &external_links.allowlist {
+        if host.is_empty() || host.contains(['/', ':']) {
+            return Err(ForgeError::Config(format!(
+                "external_links.allowlist takes host names like \"example.com\", got \"{host}\""
+            )));
+        }
+    }
+    validate_permalink("build.post_permalink", &config.build.post_permalink)?;
+    for (name, section) in &config.sections {
+        if name == "posts" || name == "pages" {
+            return Err(ForgeError::Config(format!(
+                "'{name}' is a built-in content type and cannot be configured as a section"
+            )));
+        }
+        if section.paginate_by == Some(0) {
+            return Err(ForgeError::Config(format!(
+                "sections.{name}.paginate_by must be greater than 0"
+            )));
+        }
+        if let Some(pattern) = &section.permalink {
+            validate_permalink(&format!("sections.{name}.permalink"), pattern)?;
+        }
+    }
+    Ok(())
+}
+
+/// Configured permalink patterns must tell posts apart
+fn validate_permalink(key: &str, pattern: &str) -> ForgeResult<()> {
+    if !pattern.contains(":slug") && !pattern.contains(":title") {
+        return Err(ForgeError::Config(format!(
+            "{key} must contain :slug or :title, got \"{pattern}\""
+        )));
+    }
     Ok(())
 }
@@ -1,5 +1,6 @@
+use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,6 +28,11 @@ pub struct SiteConfig {
     #[serde(default = "default_theme")]
     pub theme: String,
 
+    /// IANA timezone (e.g. "Europe/Berlin") that front matter dates without
+    /// an offset are in and that templates and feeds render dates in
+    #[serde(default = "default_timezone")]
+    pub timezone: Tz,
+
     /// Build configuration
     #[serde(default)]
     pub build: BuildConfig,
@@ -35,10 +41,34 @@ pub struct SiteConfig {
     #[serde(default = "default_taxonomies")]
     pub taxonomies: Vec<TaxonomyConfig>,
 
+    /// Content sections beyond posts and pages, keyed by directory name
+    #[serde(default)]
+    pub sections: BTreeMap<String, SectionConfig>,
+
     /// i18n configuration
     #[serde(default)]
     pub i18n: I18nConfig,
 
+    /// Responsive image processing
+    #[serde(default)]
+    pub images: ImageConfig,
+
+    /// Admonition types beyond GitHub's, or new titles for those
+    #[serde(default)]
+    pub admonitions: Vec<AdmonitionConfig>,
+
+    /// Processors that replace fenced code blocks, keyed by block language
+    #[serde(default)]
+    pub code_processors: BTreeMap<String, CodeProcessorConfig>,
+
+    /// Markdown extensions
+    #[serde(default)]
+    pub markdown: MarkdownConfig,
+
+    /// How links to other sites are rendered
+    #[serde(default)]
+    pub external_links: ExternalLinksConfig,
+
     /// Arbitrary extra data available in templates
     #[serde(default)]
     pub extra: HashMap<String, toml::Value>,
@@ -62,6 +92,10 @@ pub struct BuildConfig {
     #[serde(default = "default_static_dir")]
     pub static_dir: PathBuf,
 
+    /// Permalink pattern for posts, e.g. "/:year/:month/:slug/"
+    #[serde(default = "default_post_permalink")]
+    pub post_permalink: String,
+
     /// Number of posts per page
     #[serde(default = "default_posts_per_page")]
     pub posts_per_page: usize,
@@ -70,6 +104,20 @@ pub struct BuildConfig {
     #[serde(default)]
     pub include_drafts: bool,
 
+    /// Whether to include posts dated in the future
+    #[serde(default)]
+    pub include_future: bool,
+
+    /// Whether to take post dates and slugs from Jekyll-style
+    /// `2021-06-14-hello-world.md` filenames when front matter omits them
+    #[serde(default)]
+    pub filename_dates: bool,
+
+    /// Whether to derive post dates and `updated` dates from the git history
+    /// of each source file when front matter omits them
+    #[serde(default)]
+    pub git_dates: bool,
+
     /// Whether to generate RSS feed
     #[serde(default = "default_true")]
     pub generate_feed: bool,
@@ -82,6 +130,23 @@ pub struct BuildConfig {
     #[serde(default = "default_true")]
     pub generate_search_index: bool,
 
+    /// Whether to write `link_graph.json` with the links between posts and pages
+    #[serde(default)]
+    pub generate_link_graph: bool,
+
+    /// Whether to record a redirect in `redirects.toml` when a post's
+    /// published permalink changes between builds
+    #[serde(default)]
+    pub auto_redirects: bool,
+
+    /// Whether to write a Netlify `_redirects` file for aliases
+    #[serde(default)]
+    pub generate_netlify_redirects: bool,
+
+    /// Whether to write an nginx `map` of aliases to `nginx-redirects.conf`
+    #[serde(default)]
+    pub generate_nginx_redirects: bool,
+
     /// Whether to enable syntax highlighting
     #[serde(default = "default_true")]
     pub syntax_highlighting: bool,
@@ -90,9 +155,30 @@ pub struct BuildConfig {
     #[serde(default = "default_syntax_theme")]
     pub syntax_theme: String,
 
+    /// Theme for `prefers-color-scheme: dark` in the generated stylesheet
+    #[serde(default)]
+    pub syntax_theme_dark: Option<String>,
+
+    /// Whether highlighted code is colored by inline styles or CSS classes
+    #[serde(default)]
+    pub syntax_style: HighlightStyle,
+
+    /// Whether to render `$...$` and `$$...$$` TeX math as MathML. Off by
+    /// default, as it turns prose like "$20/$30" into math
+    #[serde(default)]
+    pub math: bool,
+
     /// Whether to generate TOC
     #[serde(default = "default_true")]
     pub generate_toc: bool,
+
+    /// Shallowest heading level in tables of contents
+    #[serde(default = "default_toc_min_level")]
+    pub toc_min_level: u32,
+
+    /// Deepest heading level in tables of contents
+    #[serde(default = "default_toc_max_level")]
+    pub toc_max_level: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -113,6 +199,252 @@ pub struct TaxonomyConfig {
     pub feed: bool,
 }
 
+/// An admonition type, written `> [!NAME]` in markdown
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdmonitionConfig {
+    /// Type name, matched case-insensitively (e.g., "question")
+    pub name: String,
+
+    /// Title shown when the admonition has none (defaults to the capitalized name)
+    #[serde(default)]
+    pub title: Option<String>,
+}
+
+/// How fenced code blocks of one language are turned into HTML
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CodeProcessorConfig {
+    /// Program and arguments that get the block on stdin and print HTML or
+    /// SVG, e.g. `["dot", "-Tsvg"]`
+    #[serde(default)]
+    pub command: Vec<String>,
+
+    /// Renderer built into forge, instead of a command
+    #[serde(default)]
+    pub builtin: Option<BuiltinProcessor>,
+}
+
+/// Code block processors that need no external program
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BuiltinProcessor {
+    /// The block is HTML or SVG to insert as it is
+    Raw,
+    /// The block is an ASCII diagram, drawn as SVG by svgbob
+    Svgbob,
+}
+
+/// Markdown syntax beyond CommonMark. Posts and pages can override each
+/// setting in their front matter.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MarkdownConfig {
+    /// GitHub-style tables
+    #[serde(default = "default_true")]
+    pub tables: bool,
+
+    /// Footnotes (`[^1]`)
+    #[serde(default = "default_true")]
+    pub footnotes: bool,
+
+    /// `~~strikethrough~~`
+    #[serde(default = "default_true")]
+    pub strikethrough: bool,
+
+    /// Task lists (`- [x] done`)
+    #[serde(default = "default_true")]
+    pub tasklists: bool,
+
+    /// Curly quotes, dashes from `--` and `---`, and ellipses from `...`
+    #[serde(default)]
+    pub smart_punctuation: bool,
+
+    /// Ids, classes and attributes on headings: `## Title {#id .class}`
+    #[serde(default)]
+    pub heading_attributes: bool,
+
+    /// Definition lists: a term, then lines starting with `: `
+    #[serde(default)]
+    pub definition_lists: bool,
+
+    /// `^superscript^`
+    #[serde(default)]
+    pub superscript: bool,
+
+    /// `~subscript~`; strikethrough then takes two tildes
+    #[serde(default)]
+    pub subscript: bool,
+
+    /// Links to other content by name: `[[post-slug]]`. Off by default, as
+    /// an unresolved one fails the build and prose like `[[1,2],[3]]` would
+    #[serde(default)]
+    pub wiki_links: bool,
+
+    /// Links from bare `https://` and `www.` URLs
+    #[serde(default)]
+    pub gfm_autolinks: bool,
+}
+
+impl Default for MarkdownConfig {
+    fn default() -> Self {
+        Self {
+            tables: true,
+            footnotes: true,
+            strikethrough: true,
+            tasklists: true,
+            smart_punctuation: false,
+            heading_attributes: false,
+            definition_lists: false,
+            superscript: false,
+            subscript: false,
+            wiki_links: false,
+            gfm_autolinks: false,
+        }
+    }
+}
+
+/// Attributes given to links whose host is not the site's. Links to hosts
+/// in the allowlist, or their subdomains, are left as they are.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExternalLinksConfig {
+    /// `rel` values, e.g. `["noopener", "nofollow"]`
+    #[serde(default)]
+    pub rel: Vec<String>,
+
+    /// Whether external links open in a new tab; implies `rel="noopener"`
+    #[serde(default)]
+    pub target_blank: bool,
+
+    /// Class of external links, for styling them
+    #[serde(default)]
+    pub class: Option<String>,
+
+    /// Hosts the policy does not apply to, e.g. `["github.com"]`
+    #[serde(default)]
+    pub allowlist: Vec<String>,
+}
+
+/// Front matter overrides of `[markdown]` settings, for a single document
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MarkdownOverrides {
+    #[serde(default)]
+    pub tables: Option<bool>,
+    #[serde(default)]
+    pub footnotes: Option<bool>,
+    #[serde(default)]
+    pub strikethrough: Option<bool>,
+    #[serde(default)]
+    pub tasklists: Option<bool>,
+    #[serde(default)]
+    pub smart_punctuation: Option<bool>,
+    #[serde(default)]
+    pub heading_attributes: Option<bool>,
+    #[serde(default)]
+    pub definition_lists: Option<bool>,
+    #[serde(default)]
+    pub superscript: Option<bool>,
+    #[serde(default)]
+    pub subscript: Option<bool>,
+    #[serde(default)]
+    pub wiki_links: Option<bool>,
+    #[serde(default)]
+    pub gfm_autolinks: Option<bool>,
+}
+
+impl MarkdownOverrides {
+    /// The site settings with these overrides applied
+    pub fn apply(&self, site: &MarkdownConfig) -> MarkdownConfig {
+        MarkdownConfig {
+            tables: self.tables.unwrap_or(site.tables),
+            footnotes: self.footnotes.unwrap_or(site.footnotes),
+            strikethrough: self.strikethrough.unwrap_or(site.strikethrough),
+            tasklists: self.tasklists.unwrap_or(site.tasklists),
+            smart_punctuation: self.smart_punctuation.unwrap_or(site.smart_punctuation),
+            heading_attributes: self.heading_attributes.unwrap_or(site.heading_attributes),
+            definition_lists: self.definition_lists.unwrap_or(site.definition_lists),
+            superscript: self.superscript.unwrap_or(site.superscript),
+            subscript: self.subscript.unwrap_or(site.subscript),
+            wiki_links: self.wiki_links.unwrap_or(site.wiki_links),
+            gfm_autolinks: self.gfm_autolinks.unwrap_or(site.gfm_autolinks),
+        }
+    }
+}
+
+/// Settings of a content section (`content/<name>/`). Every field is
+/// optional so that `forge.toml` and the section's `_index.md` front matter
+/// can be merged.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct SectionConfig {
+    /// Section title (defaults to the capitalized directory name)
+    #[serde(default)]
+    pub title: Option<String>,
+
+    /// Section description
+    #[serde(default)]
+    pub description: Option<String>,
+
+    /// Template for the section listing (defaults to "section.html")
+    #[serde(default)]
+    pub template: Option<String>,
+
+    /// Template for the section's entries (defaults to "post.html")
+    #[serde(default)]
+    pub page_template: Option<String>,
+
+    /// Sort order of the entries (defaults to date)
+    #[serde(default)]
+    pub sort_by: Option<SortBy>,
+
+    /// Entries per listing page (defaults to build.posts_per_page)
+    #[serde(default)]
+    pub paginate_by: Option<usize>,
+
+    /// Permalink pattern of the entries (defaults to "/:section/:slug/")
+    #[serde(default)]
+    pub permalink: Option<String>,
+
+    /// Whether to generate an RSS feed at `<section>/feed.xml`
+    #[serde(default)]
+    pub feed: Option<bool>,
+}
+
+impl SectionConfig {
+    /// Fill every unset field from `fallback`.
+    pub fn or(self, fallback: SectionConfig) -> SectionConfig {
+        SectionConfig {
+            title: self.title.or(fallback.title),
+            description: self.description.or(fallback.description),
+            template: self.template.or(fallback.template),
+            page_template: self.page_template.or(fallback.page_template),
+            sort_by: self.sort_by.or(fallback.sort_by),
+            paginate_by: self.paginate_by.or(fallback.paginate_by),
+            permalink: self.permalink.or(fallback.permalink),
+            feed: self.feed.or(fallback.feed),
+        }
+    }
+}
+
+/// How highlighted code gets its colors
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HighlightStyle {
+    /// `style` attributes from the syntax theme
+    #[default]
+    Inline,
+    /// `syn-` prefixed classes, colored by a generated `css/syntax-theme.css`
+    Classes,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortBy {
+    /// Newest first
+    #[default]
+    Date,
+    /// Alphabetical by title
+    Title,
+    /// Ascending `weight` front matter value
+    Weight,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct I18nConfig {
     /// Available languages
@@ -128,6 +460,48 @@ pub struct I18nConfig {
     pub translations_dir: PathBuf,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageConfig {
+    /// Whether to give images in markdown resized variants and `srcset`.
+    /// Off by default, as it rewrites image markup and adds image processing
+    /// to every build
+    #[serde(default)]
+    pub responsive: bool,
+
+    /// Widths of the variants generated for responsive images
+    #[serde(default = "default_image_widths")]
+    pub widths: Vec<u32>,
+
+    /// `sizes` attribute of responsive images
+    #[serde(default = "default_image_sizes")]
+    pub sizes: String,
+
+    /// Whether to also offer lossless WebP variants of responsive PNG images
+    #[serde(default = "default_true")]
+    pub webp: bool,
+
+    /// JPEG quality of resized images, 1-100
+    #[serde(default = "default_image_quality")]
+    pub quality: u8,
+
+    /// Whether images in markdown load lazily
+    #[serde(default = "default_true")]
+    pub lazy: bool,
+}
+
+impl Default for ImageConfig {
+    fn default() -> Self {
+        Self {
+            responsive: false,
+            widths: default_image_widths(),
+            sizes: default_image_sizes(),
+            webp: true,
+            quality: default_image_quality(),
+            lazy: true,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LanguageConfig {
     pub code: String,
@@ -148,6 +522,10 @@ fn default_theme() -> String {
     "default".to_string()
 }
 
+fn default_timezone() -> Tz {
+    Tz::UTC
+}
+
 fn default_output_dir() -> PathBuf {
     PathBuf::from("public")
 }
@@ -168,6 +546,10 @@ fn default_i18n_dir() -> PathBuf {
     PathBuf::from("i18n")
 }
 
+fn default_post_permalink() -> String {
+    "/posts/:slug/".to_string()
+}
+
 fn default_posts_per_page() -> usize {
     10
 }
@@ -176,6 +558,26 @@ fn default_true() -> bool {
     true
 }
 
+fn default_image_widths() -> Vec<u32> {
+    vec![480, 800, 1200, 1600]
+}
+
+fn default_image_sizes() -> String {
+    "(max-width: 800px) 100vw, 800px".to_string()
+}
+
+fn default_image_quality() -> u8 {
+    80
+}
+
+fn default_toc_min_level() -> u32 {
+    1
+}
+
+fn default_toc_max_level() -> u32 {
+    6
+}
+
 fn default_syntax_theme() -> String {
     "base16-ocean.dark".to_string()
 }
@@ -206,9 +608,16 @@ impl Default for SiteConfig {
             author: String::new(),
             description: String::new(),
             theme: default_theme(),
+            timezone: default_timezone(),
             build: BuildConfig::default(),
             taxonomies: default_taxonomies(),
+            sections: BTreeMap::new(),
             i18n: I18nConfig::default(),
+            images: ImageConfig::default(),
+            admonitions: Vec::new(),
+            code_processors: BTreeMap::new(),
+            markdown: MarkdownConfig::default(),
+            external_links: ExternalLinksConfig::default(),
             extra: HashMap::new(),
         }
     }
@@ -221,14 +630,27 @@ impl Default for BuildConfig {
             content_dir: default_content_dir(),
             templates_dir: default_templates_dir(),
             static_dir: default_static_dir(),
+            post_permalink: default_post_permalink(),
             posts_per_page: default_posts_per_page(),
             include_drafts: false,
+            include_future: false,
+            filename_dates: false,
+            git_dates: false,
             generate_feed: true,
             generate_sitemap: true,
             generate_search_index: true,
+            generate_link_graph: false,
+            auto_redirects: false,
+            generate_netlify_redirects: false,
+            generate_nginx_redirects: false,
             syntax_highlighting: true,
             syntax_theme: default_syntax_theme(),
+            syntax_theme_dark: None,
+            syntax_style: HighlightStyle::default(),
+            math: false,
             generate_toc: true,
+            toc_min_level: default_toc_min_level(),
+            toc_max_level: default_toc_max_level(),
         }
     }
 }
@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+
+use pulldown_cmark::{Event, Tag, TagEnd};
+use tera::{Context, Tera};
+
+use crate::config::types::AdmonitionConfig;
+use crate::content::markdown::{html_escape, MarkdownError, SourceEvent};
+use crate::content::shortcode::error_chain;
+use crate::content::transform::{
+    html_event, replace_elements, to_html, EventTransformer, RenderContext,
+};
+
+/// Template that renders admonitions, if the site or theme has one
+pub const ADMONITION_TEMPLATE: &str = "partials/admonition.html";
+
+/// The alert types GitHub renders
+const GITHUB_TYPES: [&str; 5] = ["note", "tip", "important", "warning", "caution"];
+
+/// A blockquote that starts with a `[!TYPE]` marker, as in
+///
+/// ```markdown
+/// > [!WARNING] Optional title
+/// > Text of the admonition.
+/// ```
+#[derive(Debug, Clone)]
+struct Admonition {
+    /// Lowercase type name
+    kind: String,
+    /// Title as HTML
+    title: String,
+}
+
+/// Recognizes and renders admonitions of GitHub's types and the ones
+/// declared in config.
+pub struct Admonitions<'a> {
+    /// Default titles by type name
+    titles: HashMap<String, String>,
+    /// Tera engine, when it has the admonition template
+    tera: Option<&'a Tera>,
+}
+
+impl<'a> Admonitions<'a> {
+    pub fn new(config: &[AdmonitionConfig], tera: &'a Tera) -> Self {
+        let mut titles: HashMap<String, String> = GITHUB_TYPES
+            .iter()
+            .map(|kind| (kind.to_string(), capitalize(kind)))
+            .collect();
+        for admonition in config {
+            let kind = admonition.name.to_lowercase();
+            let title = admonition
+                .title
+                .clone()
+                .unwrap_or_else(|| capitalize(&kind));
+            titles.insert(kind, title);
+        }
+
+        let has_template = tera
+            .get_template_names()
+            .any(|name| name == ADMONITION_TEMPLATE);
+        Self {
+            titles,
+            tera: has_template.then_some(tera),
+        }
+    }
+
+    /// Replace the blockquotes among events that are admonitions,
+    /// including nested ones, with their rendered HTML
+    fn replace_blockquotes<'e>(
+        &self,
+        events: Vec<SourceEvent<'e>>,
+        context: &RenderContext,
+    ) -> Result<Vec<SourceEvent<'e>>, MarkdownError> {
+        replace_elements(
+            events,
+            |event| matches!(event, Event::Start(Tag::BlockQuote(_))),
+            |mut blockquote| {
+                let end = blockquote.pop().expect("blockquotes have an end event");
+                let start = blockquote.remove(0);
+
+                let Some((admonition, len, paragraph_continues)) = self.marker(&blockquote) else {
+                    let mut output = vec![start];
+                    output.extend(self.replace_blockquotes(blockquote, context)?);
+                    output.push(end);
+                    return Ok(output);
+                };
+
+                let mut rest = blockquote.split_off(len);
+                if paragraph_continues {
+                    // Keep the paragraph, without the marker line
+                    rest.insert(0, blockquote.swap_remove(0));
+                }
+                let body = to_html(self.replace_blockquotes(rest, context)?);
+                let range = start.1;
+                match self.render(&admonition, &body) {
+                    Ok(html) => Ok(vec![html_event(html, range)]),
+                    Err(message) => Err(context.error(&range, message)),
+                }
+            },
+        )
+    }
+
+    /// Parse the marker line at the start of a blockquote's events. Returns
+    /// the admonition, the number of events making up the marker, and
+    /// whether the first paragraph goes on after it.
+    fn marker(&self, events: &[SourceEvent]) -> Option<(Admonition, usize, bool)> {
+        let event = |i: usize| events.get(i).map(|(event, _)| event);
+        if !matches!(event(0), Some(Event::Start(Tag::Paragraph))) {
+            return None;
+        }
+        // Brackets may split the line into several text events
+        let mut line = String::new();
+        let mut len = 1;
+        while let Some(Event::Text(text)) = event(len) {
+            line.push_str(text);
+            len += 1;
+        }
+        let paragraph_continues = match event(len) {
+            Some(Event::SoftBreak) => true,
+            Some(Event::End(TagEnd::Paragraph)) => false,
+            _ => return None,
+        };
+
+        let (kind, title) = line.trim().strip_prefix("[!")?.split_once(']')?;
+        let kind = kind.to_lowercase();
+        let default_title = self.titles.get(&kind)?;
+        let title = match title.trim() {
+            "" => default_title.clone(),
+            title => title.to_string(),
+        };
+
+        let admonition = Admonition {
+            kind,
+            title: html_escape(&title),
+        };
+        Some((admonition, len + 1, paragraph_continues))
+    }
+
+    /// Render an admonition around its rendered body, with the admonition
+    /// template if there is one
+    fn render(&self, admonition: &Admonition, body: &str) -> Result<String, String> {
+        let Some(tera) = self.tera else {
+            return Ok(format!(
+                "<aside class=\"admonition admonition-{kind}\">\n<p class=\"admonition-title\">{title}</p>\n{body}</aside>\n",
+                kind = admonition.kind,
+                title = admonition.title,
+            ));
+        };
+
+        let mut context = Context::new();
+        context.insert("kind", &admonition.kind);
+        context.insert("title", &admonition.title);
+        context.insert("body", body);
+        tera.render(ADMONITION_TEMPLATE, &context)
+            .map_err(|e| format!("admonition '{}': {}", admonition.kind, error_chain(&e)))
+    }
+}
+
+impl EventTransformer for Admonitions<'_> {
+    fn name(&self) -> &str {
+        "admonitions"
+    }
+
+    fn priority(&self) -> i32 {
+        90
+    }
+
+    fn transform<'e>(
+        &self,
+        events: Vec<SourceEvent<'e>>,
+        context: &mut RenderContext,
+    ) -> Result<Vec<SourceEvent<'e>>, MarkdownError> {
+        self.replace_blockquotes(events, context)
+    }
+}
+
+fn capitalize(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
@@ -0,0 +1,211 @@
+use std::ops::{Range, RangeInclusive};
+
+use pulldown_cmark::{CodeBlockKind, Event, Tag};
+
+use crate::content::highlight::{HighlightedLines, Highlighter};
+use crate::content::markdown::{html_escape, MarkdownError, SourceEvent};
+use crate::content::transform::{html_event, replace_elements, EventTransformer, RenderContext};
+
+/// Attributes of a fenced code block from its info string, as in
+/// ```` ```rust,linenos,hl_lines=3-5 8,title=src/main.rs ````
+#[derive(Debug, Default)]
+pub struct CodeBlockInfo {
+    pub lang: String,
+    /// Number the lines
+    pub linenos: bool,
+    /// Number of the first line
+    pub linenostart: usize,
+    /// Lines to mark, counted from 1 within the block
+    pub hl_lines: Vec<RangeInclusive<usize>>,
+    /// Caption naming the file the code comes from
+    pub title: Option<String>,
+    /// Give the block a copy button
+    pub copy: bool,
+}
+
+impl CodeBlockInfo {
+    /// Parse an info string. The language comes first; unknown attributes
+    /// are ignored.
+    pub fn parse(info: &str) -> Self {
+        let mut block = Self {
+            linenostart: 1,
+            ..Self::default()
+        };
+
+        for (i, part) in info.split(',').map(str::trim).enumerate() {
+            match part.split_once('=') {
+                Some(("hl_lines", ranges)) => block.hl_lines = parse_ranges(ranges),
+                Some(("title", title)) => block.title = Some(title.trim().to_string()),
+                Some(("linenostart", start)) => {
+                    if let Ok(start) = start.trim().parse() {
+                        block.linenostart = start;
+                        block.linenos = true;
+                    }
+                }
+                Some(_) => {}
+                None => match part {
+                    "linenos" => block.linenos = true,
+                    "copy" => block.copy = true,
+                    lang if i == 0 => block.lang = lang.to_string(),
+                    _ => {}
+                },
+            }
+        }
+        block
+    }
+
+    /// Whether the block needs more than a plain `<pre>`
+    pub fn is_annotated(&self) -> bool {
+        self.linenos || !self.hl_lines.is_empty() || self.title.is_some() || self.copy
+    }
+
+    fn is_highlighted(&self, line: usize) -> bool {
+        self.hl_lines.iter().any(|range| range.contains(&line))
+    }
+
+    /// Render an annotated block: a `<figure>` with the optional title as
+    /// caption, a copy button for scripts to enable, and one span per line
+    /// carrying its number and highlight state. `highlighted` is the
+    /// syntax-highlighted code if highlighting is enabled.
+    pub fn render(&self, code: &str, highlighted: Option<HighlightedLines>) -> String {
+        let (pre, lines) = match highlighted {
+            Some(highlighted) => (highlighted.pre, highlighted.lines),
+            None => ("<pre>".to_string(), code.lines().map(html_escape).collect()),
+        };
+
+        let lang = html_escape(&self.lang);
+        let mut html = String::from("<figure class=\"code-block\"");
+        if !lang.is_empty() {
+            html.push_str(&format!(" data-lang=\"{lang}\""));
+        }
+        html.push_str(">\n");
+        if let Some(title) = &self.title {
+            html.push_str(&format!(
+                "<figcaption class=\"code-title\">{}</figcaption>\n",
+                html_escape(title)
+            ));
+        }
+        html.push_str("<button type=\"button\" class=\"code-copy\" hidden>Copy</button>\n");
+
+        html.push_str(&pre);
+        if lang.is_empty() {
+            html.push_str("<code>");
+        } else {
+            html.push_str(&format!("<code class=\"language-{lang}\">"));
+        }
+        for (i, line) in lines.iter().enumerate() {
+            let class = if self.is_highlighted(i + 1) {
+                "code-line hl"
+            } else {
+                "code-line"
+            };
+            html.push_str(&format!("<span class=\"{class}\">"));
+            if self.linenos {
+                html.push_str(&format!(
+                    "<span class=\"code-lineno\">{}</span>",
+                    self.linenostart + i
+                ));
+            }
+            html.push_str(line);
+            html.push_str("</span>\n");
+        }
+        html.push_str("</code></pre>\n</figure>\n");
+        html
+    }
+}
+
+/// Split the events of a code block into its attributes, its code and the
+/// source range of the block
+pub fn code_block(events: Vec<SourceEvent>) -> (CodeBlockInfo, String, Range<usize>) {
+    let mut info = CodeBlockInfo::default();
+    let mut code = String::new();
+    let mut block_range = 0..0;
+    for (event, range) in events {
+        match event {
+            Event::Start(Tag::CodeBlock(kind)) => {
+                block_range = range;
+                if let CodeBlockKind::Fenced(info_string) = kind {
+                    info = CodeBlockInfo::parse(&info_string);
+                }
+            }
+            Event::Text(text) => code.push_str(&text),
+            _ => {}
+        }
+    }
+    (info, code, block_range)
+}
+
+/// Highlights code blocks, and renders the annotated ones as figures
+pub struct CodeBlocks<'a> {
+    highlighter: &'a Highlighter,
+    syntax_highlighting: bool,
+}
+
+impl<'a> CodeBlocks<'a> {
+    pub fn new(highlighter: &'a Highlighter, syntax_highlighting: bool) -> Self {
+        Self {
+            highlighter,
+            syntax_highlighting,
+        }
+    }
+
+    fn render(&self, info: &CodeBlockInfo, code: &str) -> String {
+        let lang = &info.lang;
+        let highlight = self.syntax_highlighting && !lang.is_empty();
+
+        if info.is_annotated() {
+            let highlighted = highlight
+                .then(|| self.highlighter.highlight_lines(code, lang))
+                .flatten();
+            return info.render(code, highlighted);
+        }
+        if highlight {
+            return match self.highlighter.highlight(code, lang) {
+                Some(highlighted) => highlighted,
+                None => format!("<pre><code>{}</code></pre>", html_escape(code)),
+            };
+        }
+        let class = if lang.is_empty() {
+            String::new()
+        } else {
+            format!(" class=\"language-{}\"", html_escape(lang))
+        };
+        format!("<pre><code{class}>{}</code></pre>\n", html_escape(code))
+    }
+}
+
+impl EventTransformer for CodeBlocks<'_> {
+    fn name(&self) -> &str {
+        "code_blocks"
+    }
+
+    fn priority(&self) -> i32 {
+        40
+    }
+
+    fn transform<'e>(
+        &self,
+        events: Vec<SourceEvent<'e>>,
+        _context: &mut RenderContext,
+    ) -> Result<Vec<SourceEvent<'e>>, MarkdownError> {
+        replace_elements(
+            events,
+            |event| matches!(event, Event::Start(Tag::CodeBlock(_))),
+            |block| {
+                let (info, code, range) = code_block(block);
+                Ok(vec![html_event(self.render(&info, &code), range)])
+            },
+        )
+    }
+}
+
+/// Line ranges like `3-5 8`; separators may be spaces or `;`
+fn parse_ranges(ranges: &str) -> Vec<RangeInclusive<usize>> {
+    ranges
+        .split([' ', ';'])
+        .filter_map(|range| {
+            let (start, end) = range.split_once('-').unwrap_or((range, range));
+            Some(start.trim().parse().ok()?..=end.trim().parse().ok()?)
+        })
+        .collect()
+}
@@ -0,0 +1,146 @@
+use pulldown_cmark::{Event, LinkType, Tag, TagEnd};
+
+use crate::config::types::ExternalLinksConfig;
+use crate::config::SiteConfig;
+use crate::content::markdown::{html_escape, MarkdownError, SourceEvent};
+use crate::content::transform::{to_html, EventTransformer, RenderContext};
+
+/// Finds links to other sites, collects their URLs and gives them the
+/// attributes of the `[external_links]` policy. A link is external when its
+/// host differs from the host of `base_url`.
+pub struct ExternalLinks {
+    site_host: Option<String>,
+    /// Attributes added to the `<a>` tag, empty when there is no policy
+    attributes: String,
+    allowlist: Vec<String>,
+}
+
+impl ExternalLinks {
+    pub fn new(config: &SiteConfig) -> Self {
+        Self {
+            site_host: url_host(&config.base_url),
+            attributes: attributes(&config.external_links),
+            allowlist: config
+                .external_links
+                .allowlist
+                .iter()
+                .map(|host| host.trim_start_matches("*.").to_lowercase())
+                .collect(),
+        }
+    }
+
+    /// Whether the policy applies to links to a host
+    fn applies_to(&self, host: &str) -> bool {
+        !self.attributes.is_empty()
+            && !self.allowlist.iter().any(|allowed| {
+                host == allowed
+                    || host
+                        .strip_suffix(allowed.as_str())
+                        .is_some_and(|sub| sub.ends_with('.'))
+            })
+    }
+}
+
+impl EventTransformer for ExternalLinks {
+    fn name(&self) -> &str {
+        "external_links"
+    }
+
+    fn priority(&self) -> i32 {
+        55
+    }
+
+    fn transform<'e>(
+        &self,
+        events: Vec<SourceEvent<'e>>,
+        context: &mut RenderContext,
+    ) -> Result<Vec<SourceEvent<'e>>, MarkdownError> {
+        // A link written as HTML needs its end as HTML too, so that starts
+        // and ends stay balanced for later transformers. Links do not nest.
+        let mut in_rewritten = false;
+        Ok(events
+            .into_iter()
+            .map(|(event, range)| {
+                if in_rewritten && matches!(event, Event::End(TagEnd::Link)) {
+                    in_rewritten = false;
+                    return (Event::InlineHtml("</a>".into()), range);
+                }
+                let Event::Start(Tag::Link {
+                    link_type,
+                    dest_url,
+                    ..
+                }) = &event
+                else {
+                    return (event, range);
+                };
+                let host = match link_type {
+                    LinkType::Email => None,
+                    _ => url_host(dest_url),
+                };
+                let Some(host) = host.filter(|host| Some(host) != self.site_host.as_ref()) else {
+                    return (event, range);
+                };
+
+                if !context
+                    .external_links
+                    .iter()
+                    .any(|url| url == dest_url.as_ref())
+                {
+                    context.external_links.push(dest_url.to_string());
+                }
+                if !self.applies_to(&host) {
+                    return (event, range);
+                }
+                // The HTML writer escapes the link's own attributes
+                let tag = to_html([(event, range.clone())]);
+                let tag = tag.strip_suffix('>').unwrap_or(&tag);
+                let html = format!("{tag}{}>", self.attributes);
+                in_rewritten = true;
+                (Event::InlineHtml(html.into()), range)
+            })
+            .collect())
+    }
+}
+
+/// Attributes for the `<a>` tags of external links
+fn attributes(config: &ExternalLinksConfig) -> String {
+    let mut rel: Vec<&str> = Vec::new();
+    for value in &config.rel {
+        if !rel.contains(&value.as_str()) {
+            rel.push(value);
+        }
+    }
+    if config.target_blank && !rel.contains(&"noopener") {
+        rel.push("noopener");
+    }
+
+    let mut attributes = String::new();
+    if !rel.is_empty() {
+        attributes.push_str(&format!(" rel=\"{}\"", html_escape(&rel.join(" "))));
+    }
+    if config.target_blank {
+        attributes.push_str(" target=\"_blank\"");
+    }
+    if let Some(class) = config.class.as_deref().filter(|class| !class.is_empty()) {
+        attributes.push_str(&format!(" class=\"{}\"", html_escape(class)));
+    }
+    attributes
+}
+
+/// Lowercased host of an `http://`, `https://` or protocol-relative URL
+fn url_host(url: &str) -> Option<String> {
+    let url = url.to_lowercase();
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .or_else(|| url.strip_prefix("//"))?;
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host = authority.rsplit('@').next().unwrap_or_default();
+    let host = match host.strip_prefix('[') {
+        // IPv6 addresses contain colons
+        Some(address) => address.split(']').next().unwrap_or_default(),
+        None => host.split(':').next().unwrap_or_default(),
+    };
+    let host = host.trim_end_matches('.');
+    (!host.is_empty()).then(|| host.to_string())
+}
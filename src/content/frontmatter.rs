@@ -1,15 +1,27 @@
-use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, TimeZone};
+use chrono_tz::Tz;
+use serde::de::{self, DeserializeOwned, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::collections::HashMap;
 
+use crate::config::types::MarkdownOverrides;
+use crate::content::permalink;
 use crate::error::{ForgeError, ForgeResult};
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct FrontMatter {
     pub title: String,
 
-    #[serde(default)]
-    pub date: Option<DateTime<Utc>>,
+    #[serde(default, deserialize_with = "deserialize_date")]
+    pub date: Option<FrontMatterDate>,
+
+    /// Date of the last significant change
+    #[serde(default, deserialize_with = "deserialize_date")]
+    pub updated: Option<FrontMatterDate>,
+
+    /// Date after which the content is left out of the build
+    #[serde(default, deserialize_with = "deserialize_date")]
+    pub expiry_date: Option<FrontMatterDate>,
 
     #[serde(default)]
     pub draft: bool,
@@ -17,6 +29,14 @@ pub struct FrontMatter {
     #[serde(default)]
     pub slug: Option<String>,
 
+    /// Permalink pattern or path overriding the configured one
+    #[serde(default)]
+    pub permalink: Option<String>,
+
+    /// Old URL paths that should redirect to this post
+    #[serde(default)]
+    pub aliases: Vec<String>,
+
     #[serde(default)]
     pub description: Option<String>,
 
@@ -32,38 +52,237 @@ pub struct FrontMatter {
     #[serde(default)]
     pub template: Option<String>,
 
+    /// Whether to build a table of contents, overriding `generate_toc`
+    #[serde(default)]
+    pub toc: Option<bool>,
+
+    /// Shallowest heading level in the table of contents
+    #[serde(default)]
+    pub toc_min_level: Option<u32>,
+
+    /// Deepest heading level in the table of contents
+    #[serde(default)]
+    pub toc_max_level: Option<u32>,
+
+    /// Whether to render TeX math, overriding `math`
+    #[serde(default)]
+    pub math: Option<bool>,
+
+    /// Markdown extensions that differ from the site's `[markdown]` settings
+    #[serde(default)]
+    pub markdown: MarkdownOverrides,
+
+    /// Ordering key for sections sorted by weight
+    #[serde(default)]
+    pub weight: i32,
+
     #[serde(default)]
     pub extra: HashMap<String, serde_json::Value>,
 }
 
-/// Split content at `---` delimiters and parse the YAML front matter.
+/// A date as written in front matter: either a point in time with an explicit
+/// offset, or a local date and time in the site timezone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum FrontMatterDate {
+    /// RFC 3339, e.g. `2024-03-01T09:00:00+01:00`
+    Offset(DateTime<FixedOffset>),
+    /// `2024-03-01`, `2024-03-01 09:00` or `2024-03-01T09:00:00`
+    Local(NaiveDateTime),
+}
+
+impl FrontMatterDate {
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+            return Some(Self::Offset(date));
+        }
+        const LOCAL_FORMATS: [&str; 4] = [
+            "%Y-%m-%dT%H:%M:%S%.f",
+            "%Y-%m-%d %H:%M:%S%.f",
+            "%Y-%m-%dT%H:%M",
+            "%Y-%m-%d %H:%M",
+        ];
+        LOCAL_FORMATS
+            .iter()
+            .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+            .or_else(|| {
+                NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                    .ok()
+                    .map(|date| date.and_time(Default::default()))
+            })
+            .map(Self::Local)
+    }
+
+    /// The date in the given timezone. Local times are read as times in that
+    /// timezone; one skipped by a DST change is taken as UTC.
+    pub fn in_timezone(&self, timezone: &Tz) -> DateTime<FixedOffset> {
+        match self {
+            Self::Offset(date) => date.with_timezone(timezone).fixed_offset(),
+            Self::Local(naive) => timezone
+                .from_local_datetime(naive)
+                .earliest()
+                .unwrap_or_else(|| timezone.from_utc_datetime(naive))
+                .fixed_offset(),
+        }
+    }
+}
+
+/// Split off the front matter block and parse it. The format is detected
+/// from the opening delimiter: `---` for YAML, `+++` for TOML, or a leading
+/// `{` for a JSON object.
 /// Returns (FrontMatter, body_content).
 pub fn parse_front_matter(content: &str, path: &str) -> ForgeResult<(FrontMatter, String)> {
+    let (fm, body) = parse_front_matter_as::<FrontMatter>(content, path)?;
+
+    // These become output paths, which must stay inside the output directory
+    let output_paths = fm.slug.iter().chain(&fm.permalink).chain(&fm.aliases);
+    for value in output_paths {
+        if let Err(message) = permalink::check_path(value) {
+            let message = match content.find(value.as_str()) {
+                Some(pos) => {
+                    let line = content[..pos].matches('\n').count() + 1;
+                    format!("invalid path at line {line}: {message}")
+                }
+                None => format!("invalid path: {message}"),
+            };
+            return Err(ForgeError::FrontMatter {
+                path: path.into(),
+                message,
+            });
+        }
+    }
+    Ok((fm, body))
+}
+
+/// Like `parse_front_matter`, but deserializes the front matter into any type.
+pub fn parse_front_matter_as<T: DeserializeOwned>(
+    content: &str,
+    path: &str,
+) -> ForgeResult<(T, String)> {
     let content = content.trim_start_matches('\u{feff}'); // strip BOM
+    let error = |message: String| ForgeError::FrontMatter {
+        path: path.into(),
+        message,
+    };
 
-    if !content.starts_with("---") {
-        return Err(ForgeError::FrontMatter {
-            path: path.into(),
-            message: "Missing opening --- delimiter".to_string(),
-        });
+    if content.starts_with("---") {
+        let (yaml_str, body) = split_block(content, "---").map_err(error)?;
+        let fm = serde_yaml_ng::from_str(yaml_str).map_err(|e| {
+            let location = e.location().map(|l| (l.line(), l.column()));
+            error(located("YAML", &e.to_string(), location))
+        })?;
+        Ok((fm, body))
+    } else if content.starts_with("+++") {
+        let (toml_str, body) = split_block(content, "+++").map_err(error)?;
+        let fm = toml::from_str(toml_str).map_err(|e| {
+            let location = e.span().map(|span| line_column(toml_str, span.start));
+            error(located("TOML", e.message(), location))
+        })?;
+        Ok((fm, body))
+    } else if content.starts_with('{') {
+        let mut stream = serde_json::Deserializer::from_str(content).into_iter::<T>();
+        let fm = match stream.next() {
+            Some(Ok(fm)) => fm,
+            Some(Err(e)) => {
+                let location = Some((e.line(), e.column()));
+                return Err(error(located("JSON", &e.to_string(), location)));
+            }
+            None => return Err(error("Empty JSON front matter".to_string())),
+        };
+        let body = &content[stream.byte_offset()..];
+        Ok((fm, body.trim_start().to_string()))
+    } else {
+        Err(error(
+            "Missing front matter: expected an opening ---, +++ or {".to_string(),
+        ))
     }
+}
 
-    let after_first = &content[3..];
+/// Line of the file on which `body`, as returned by `parse_front_matter`,
+/// starts.
+pub fn body_line(content: &str, body: &str) -> usize {
+    let front = content.len().saturating_sub(body.len());
+    content[..front].matches('\n').count() + 1
+}
+
+/// Split a front matter block enclosed in `delimiter` lines from the body.
+/// The block keeps the remainder of the opening line, so line numbers inside
+/// it match the line numbers of the file.
+fn split_block<'a>(content: &'a str, delimiter: &str) -> Result<(&'a str, String), String> {
+    let after_first = &content[delimiter.len()..];
     let end_pos = after_first
-        .find("\n---")
-        .ok_or_else(|| ForgeError::FrontMatter {
-            path: path.into(),
-            message: "Missing closing --- delimiter".to_string(),
-        })?;
+        .find(&format!("\n{delimiter}"))
+        .ok_or_else(|| format!("Missing closing {delimiter} delimiter"))?;
 
-    let yaml_str = &after_first[..end_pos];
-    let body = &after_first[end_pos + 4..]; // skip past \n---
+    let block = &after_first[..end_pos];
+    let body = &after_first[end_pos + 1 + delimiter.len()..];
+    Ok((block, body.trim_start_matches('\n').to_string()))
+}
 
-    let fm: FrontMatter =
-        serde_yaml_ng::from_str(yaml_str).map_err(|e| ForgeError::FrontMatter {
-            path: path.into(),
-            message: format!("YAML parse error: {e}"),
-        })?;
+/// Format a parse error with its position inside the front matter block.
+fn located(format: &str, message: &str, location: Option<(usize, usize)>) -> String {
+    // Drop the position serde_yaml and serde_json append to their messages
+    let message = match message.rsplit_once(" at line ") {
+        Some((msg, pos)) if pos.split(" column ").all(|n| n.parse::<usize>().is_ok()) => msg,
+        _ => message,
+    };
+    match location {
+        Some((line, column)) => {
+            format!("{format} parse error at line {line}, column {column}: {message}")
+        }
+        None => format!("{format} parse error: {message}"),
+    }
+}
+
+/// 1-based line and column of a byte offset
+fn line_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.len() - before.rfind('\n').map_or(0, |pos| pos + 1) + 1;
+    (line, column)
+}
+
+/// Deserialize an RFC 3339 date from a string or a native TOML datetime.
+fn deserialize_date<'de, D>(deserializer: D) -> Result<Option<FrontMatterDate>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct DateVisitor;
+
+    impl<'de> Visitor<'de> for DateVisitor {
+        type Value = Option<FrontMatterDate>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("a date (YYYY-MM-DD), a local date and time or an RFC 3339 timestamp")
+        }
+
+        fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_some<D: Deserializer<'de>>(self, d: D) -> Result<Self::Value, D::Error> {
+            d.deserialize_any(self)
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+            FrontMatterDate::parse(value)
+                .map(Some)
+                .ok_or_else(|| E::invalid_value(de::Unexpected::Str(value), &self))
+        }
+
+        // TOML datetimes arrive as a single-entry map holding their string form
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            match map.next_entry::<String, String>()? {
+                Some((_, value)) => self.visit_str(&value),
+                None => Err(de::Error::custom("expected a datetime")),
+            }
+        }
+    }
 
-    Ok((fm, body.trim_start_matches('\n').to_string()))
+    deserializer.deserialize_option(DateVisitor)
 }
@@ -0,0 +1,99 @@
+use chrono::{DateTime, Utc};
+use git2::{Delta, DiffFindOptions, Repository, Sort};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// When a file was first and last committed
+#[derive(Debug, Clone, Copy)]
+pub struct FileDates {
+    pub created: DateTime<Utc>,
+    pub updated: DateTime<Utc>,
+}
+
+/// Commit dates of every file in the history of `HEAD`, read straight from
+/// the repository containing the site.
+#[derive(Debug, Default)]
+pub struct GitHistory {
+    workdir: PathBuf,
+    files: HashMap<PathBuf, FileDates>,
+}
+
+impl GitHistory {
+    /// Walk the history of the repository containing `site_dir`. Returns
+    /// `None` if the site is not in a git repository or has no commits.
+    pub fn load(site_dir: &Path) -> Option<Self> {
+        let repo = match Repository::discover(site_dir) {
+            Ok(repo) => repo,
+            Err(e) => {
+                tracing::warn!("Not reading dates from git: {}", e.message());
+                return None;
+            }
+        };
+        let workdir = repo.workdir()?.canonicalize().ok()?;
+
+        match Self::walk(&repo) {
+            Ok(files) => Some(Self { workdir, files }),
+            Err(e) => {
+                tracing::warn!("Failed to read git history: {}", e.message());
+                None
+            }
+        }
+    }
+
+    fn walk(repo: &Repository) -> Result<HashMap<PathBuf, FileDates>, git2::Error> {
+        let mut files: HashMap<PathBuf, FileDates> = HashMap::new();
+        // Old path -> path in HEAD, for files renamed in newer commits
+        let mut renames: HashMap<PathBuf, PathBuf> = HashMap::new();
+
+        let mut revwalk = repo.revwalk()?;
+        revwalk.set_sorting(Sort::TIME)?;
+        revwalk.push_head()?;
+
+        // Newest first, so the first commit seen is the last update and the
+        // last one seen is the creation
+        for oid in revwalk {
+            let commit = repo.find_commit(oid?)?;
+            let Some(time) = DateTime::from_timestamp(commit.time().seconds(), 0) else {
+                continue;
+            };
+
+            let tree = commit.tree()?;
+            let parent_tree = match commit.parents().next() {
+                Some(parent) => Some(parent.tree()?),
+                None => None,
+            };
+            let mut diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)?;
+            diff.find_similar(Some(DiffFindOptions::new().renames(true)))?;
+
+            for delta in diff.deltas() {
+                let Some(path) = delta.new_file().path() else {
+                    continue;
+                };
+                let current = renames.get(path).cloned().unwrap_or_else(|| path.into());
+
+                if delta.status() == Delta::Renamed {
+                    if let Some(old) = delta.old_file().path() {
+                        renames.insert(old.into(), current.clone());
+                    }
+                }
+
+                files
+                    .entry(current)
+                    .and_modify(|dates| dates.created = time)
+                    .or_insert(FileDates {
+                        created: time,
+                        updated: time,
+                    });
+            }
+        }
+
+        Ok(files)
+    }
+
+    /// Dates of a source file, if it has been committed.
+    pub fn dates(&self, path: &Path) -> Option<FileDates> {
+        let path = path.canonicalize().ok()?;
+        let relative = path.strip_prefix(&self.workdir).ok()?;
+        self.files.get(relative).copied()
+    }
+}
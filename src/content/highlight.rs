@@ -0,0 +1,177 @@
+use std::path::Path;
+
+use syntect::easy::HighlightLines;
+use syntect::highlighting::ThemeSet;
+use syntect::html::{
+    css_for_theme_with_class_style, highlighted_html_for_string, line_tokens_to_classed_spans,
+    start_highlighted_html_snippet, styled_line_to_highlighted_html, ClassStyle,
+    ClassedHTMLGenerator, IncludeBackground,
+};
+use syntect::parsing::{ParseState, Scope, ScopeStack, SyntaxReference, SyntaxSet};
+use syntect::util::LinesWithEndings;
+
+use crate::config::types::HighlightStyle;
+use crate::error::{ForgeError, ForgeResult};
+
+/// Site directory with extra `.sublime-syntax` and `.tmTheme` files
+pub const SYNTAXES_DIR: &str = "syntaxes";
+
+/// Class names of highlighted code are prefixed so they cannot clash with
+/// the theme's own classes
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "syn-" };
+
+/// Code highlighted line by line, so that lines can be numbered and marked
+pub struct HighlightedLines {
+    /// Opening `<pre>` tag carrying the colors of the block
+    pub pre: String,
+    /// HTML of each line without its newline, with every tag closed
+    pub lines: Vec<String>,
+}
+
+/// Syntax highlighting with syntect's bundled syntaxes and themes, plus any
+/// found in the site's `syntaxes/` directory.
+pub struct Highlighter {
+    syntax_set: SyntaxSet,
+    theme_set: ThemeSet,
+    theme_name: String,
+    style: HighlightStyle,
+}
+
+impl Highlighter {
+    pub fn load(site_dir: &Path, theme_name: &str, style: HighlightStyle) -> ForgeResult<Self> {
+        let mut syntax_set = SyntaxSet::load_defaults_newlines();
+        let mut theme_set = ThemeSet::load_defaults();
+
+        let dir = site_dir.join(SYNTAXES_DIR);
+        if dir.is_dir() {
+            let loading_error = |e: syntect::LoadingError| {
+                ForgeError::Config(format!("Failed to load {}: {e}", dir.display()))
+            };
+            let mut builder = syntax_set.into_builder();
+            builder.add_from_folder(&dir, true).map_err(loading_error)?;
+            syntax_set = builder.build();
+            theme_set.add_from_folder(&dir).map_err(loading_error)?;
+        }
+
+        let highlighter = Self {
+            syntax_set,
+            theme_set,
+            theme_name: theme_name.to_string(),
+            style,
+        };
+        highlighter.check_theme(theme_name)?;
+        Ok(highlighter)
+    }
+
+    fn check_theme(&self, name: &str) -> ForgeResult<()> {
+        if self.theme_set.themes.contains_key(name) {
+            return Ok(());
+        }
+        let names: Vec<&str> = self.theme_set.themes.keys().map(String::as_str).collect();
+        Err(ForgeError::Config(format!(
+            "Unknown syntax theme '{name}', available: {}",
+            names.join(", ")
+        )))
+    }
+
+    /// Highlight a code block as a `<pre>` element, with inline styles or
+    /// with classes for the stylesheet from `css`.
+    pub fn highlight(&self, code: &str, lang: &str) -> Option<String> {
+        let syntax = self.syntax(lang);
+
+        match self.style {
+            HighlightStyle::Inline => {
+                let theme = &self.theme_set.themes[&self.theme_name];
+                highlighted_html_for_string(code, &self.syntax_set, syntax, theme).ok()
+            }
+            HighlightStyle::Classes => {
+                let mut generator = ClassedHTMLGenerator::new_with_class_style(
+                    syntax,
+                    &self.syntax_set,
+                    CLASS_STYLE,
+                );
+                for line in LinesWithEndings::from(code) {
+                    generator
+                        .parse_html_for_line_which_includes_newline(line)
+                        .ok()?;
+                }
+                Some(format!(
+                    "<pre class=\"syn-code\"><code>{}</code></pre>\n",
+                    generator.finalize()
+                ))
+            }
+        }
+    }
+
+    /// Highlight a code block one line at a time
+    pub fn highlight_lines(&self, code: &str, lang: &str) -> Option<HighlightedLines> {
+        let syntax = self.syntax(lang);
+        let mut lines = Vec::new();
+
+        let pre = match self.style {
+            HighlightStyle::Inline => {
+                let theme = &self.theme_set.themes[&self.theme_name];
+                let mut highlighter = HighlightLines::new(syntax, theme);
+                let (pre, background) = start_highlighted_html_snippet(theme);
+                for line in LinesWithEndings::from(code) {
+                    let regions = highlighter.highlight_line(line, &self.syntax_set).ok()?;
+                    let html = styled_line_to_highlighted_html(
+                        &regions,
+                        IncludeBackground::IfDifferent(background),
+                    )
+                    .ok()?;
+                    lines.push(strip_newline(html));
+                }
+                pre.trim_end().to_string()
+            }
+            HighlightStyle::Classes => {
+                let mut state = ParseState::new(syntax);
+                let mut stack = ScopeStack::new();
+                for line in LinesWithEndings::from(code) {
+                    let ops = state.parse_line(line, &self.syntax_set).ok()?;
+                    // Reopen the scopes left open by the previous line
+                    let mut html: String = stack.as_slice().iter().map(class_span).collect();
+                    let (spans, _) =
+                        line_tokens_to_classed_spans(line, &ops, CLASS_STYLE, &mut stack).ok()?;
+                    html.push_str(&spans);
+                    html.push_str(&"</span>".repeat(stack.len()));
+                    lines.push(strip_newline(html));
+                }
+                "<pre class=\"syn-code\">".to_string()
+            }
+        };
+
+        Some(HighlightedLines { pre, lines })
+    }
+
+    fn syntax(&self, lang: &str) -> &SyntaxReference {
+        self.syntax_set
+            .find_syntax_by_token(lang)
+            .unwrap_or_else(|| self.syntax_set.find_syntax_plain_text())
+    }
+
+    /// Stylesheet for code highlighted with classes, in the colors of a theme
+    pub fn css(&self, theme_name: &str) -> ForgeResult<String> {
+        self.check_theme(theme_name)?;
+        css_for_theme_with_class_style(&self.theme_set.themes[theme_name], CLASS_STYLE)
+            .map_err(|e| ForgeError::Build(format!("Failed to generate syntax CSS: {e}")))
+    }
+}
+
+/// Opening tag of the span for a scope, with the classes syntect gives it
+fn class_span(scope: &Scope) -> String {
+    let classes: Vec<String> = scope
+        .build_string()
+        .split('.')
+        .map(|atom| format!("syn-{atom}"))
+        .collect();
+    format!("<span class=\"{}\">", classes.join(" "))
+}
+
+/// Drop the newline ending a highlighted line; it is the last text in it
+fn strip_newline(mut html: String) -> String {
+    if let Some(pos) = html.rfind('\n') {
+        html.remove(pos);
+    }
+    html
+}
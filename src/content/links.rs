@@ -0,0 +1,528 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::content::page::Page;
+use crate::content::post::Post;
+use crate::content::section::Section;
+use crate::error::{ForgeError, ForgeResult};
+use crate::types::{Backlink, PostRef};
+
+/// Links to a source file relative to the content directory: `@/posts/foo.md`
+pub const INTERNAL_LINK_PREFIX: &str = "@/";
+
+/// Marks the target of a `[[name]]` wiki link in rendered HTML
+pub const WIKI_LINK_PREFIX: &str = "@wiki:";
+
+/// A post, page or section that links can point at
+struct LinkTarget {
+    permalink: String,
+    anchors: HashSet<String>,
+}
+
+/// Every post, page and section, by source path and by name.
+#[derive(Default)]
+struct LinkIndex {
+    targets: Vec<LinkTarget>,
+    /// Source path relative to the content directory
+    paths: HashMap<String, usize>,
+    /// Lowercased slug, file name or title, for wiki links
+    names: HashMap<String, Vec<usize>>,
+}
+
+impl LinkIndex {
+    fn add(&mut self, path: String, names: &[&str], permalink: &str, html: &str) {
+        let index = self.targets.len();
+        // Headings, footnotes and raw HTML elements with an id
+        let anchors: HashSet<String> = html_ids(html).collect();
+
+        self.targets.push(LinkTarget {
+            permalink: permalink.to_string(),
+            anchors,
+        });
+        self.paths.insert(path, index);
+
+        let mut seen = HashSet::new();
+        for name in names {
+            let name = name.to_lowercase();
+            if !name.is_empty() && seen.insert(name.clone()) {
+                self.names.entry(name).or_default().push(index);
+            }
+        }
+    }
+
+    /// Resolve an internal link target to a URL
+    fn resolve(&self, href: &str) -> Result<String, String> {
+        let (target, anchor) = match href.split_once('#') {
+            Some((target, anchor)) => (target, Some(anchor)),
+            None => (href, None),
+        };
+
+        let index = if let Some(path) = target.strip_prefix(INTERNAL_LINK_PREFIX) {
+            *self
+                .paths
+                .get(path)
+                .ok_or_else(|| "no such content file".to_string())?
+        } else {
+            let name = target.strip_prefix(WIKI_LINK_PREFIX).unwrap_or(target);
+            match self.paths.get(name) {
+                Some(index) => *index,
+                None => match self.names.get(&name.to_lowercase()).map(Vec::as_slice) {
+                    Some([index]) => *index,
+                    Some(matches) => {
+                        let permalinks: Vec<&str> = matches
+                            .iter()
+                            .map(|i| self.targets[*i].permalink.as_str())
+                            .collect();
+                        return Err(format!("ambiguous, matches {}", permalinks.join(", ")));
+                    }
+                    None => return Err("no post or page with that name".to_string()),
+                },
+            }
+        };
+
+        let target = &self.targets[index];
+        match anchor {
+            Some(anchor) if !anchor.is_empty() => {
+                if !target.anchors.contains(anchor) {
+                    return Err(format!("no heading #{anchor} in {}", target.permalink));
+                }
+                Ok(format!("{}#{anchor}", target.permalink))
+            }
+            _ => Ok(target.permalink.clone()),
+        }
+    }
+}
+
+/// A link that could not be resolved
+struct BrokenLink {
+    source_path: String,
+    link: String,
+    reason: String,
+}
+
+/// Resolve `@/path.md` and `[[name]]` links in all rendered content to
+/// permalinks, once every permalink is known. Fails with a list of every
+/// link that could not be resolved.
+pub fn resolve_site_links(
+    content_dir: &Path,
+    posts: &mut [Post],
+    pages: &mut [Page],
+    sections: &mut [Section],
+) -> ForgeResult<()> {
+    let relative = |source_path: &str| -> String {
+        Path::new(source_path)
+            .strip_prefix(content_dir)
+            .unwrap_or(Path::new(source_path))
+            .to_string_lossy()
+            .replace('\\', "/")
+    };
+
+    let mut index = LinkIndex::default();
+    let all_posts = posts
+        .iter()
+        .chain(sections.iter().flat_map(|section| &section.posts));
+    for post in all_posts {
+        let path = relative(&post.source_path);
+        let file_name = file_name(&path);
+        index.add(
+            path.clone(),
+            &[&post.slug, file_name, &post.title],
+            &post.permalink,
+            &post.content_html,
+        );
+    }
+    for page in pages.iter() {
+        let path = relative(&page.source_path);
+        let file_name = file_name(&path);
+        index.add(
+            path.clone(),
+            &[&page.slug, file_name, &page.title],
+            &page.permalink,
+            &page.content_html,
+        );
+    }
+    for section in sections.iter() {
+        index.add(
+            format!("{}/_index.md", section.name),
+            &[&section.name, &section.title],
+            &section.permalink,
+            &section.content_html,
+        );
+    }
+
+    let mut broken = Vec::new();
+    for post in posts.iter_mut().chain(
+        sections
+            .iter_mut()
+            .flat_map(|section| section.posts.iter_mut()),
+    ) {
+        resolve_content(
+            &index,
+            &post.source_path,
+            &mut post.content_html,
+            &mut post.content_hash,
+            &mut broken,
+        );
+    }
+    for page in pages.iter_mut() {
+        resolve_content(
+            &index,
+            &page.source_path,
+            &mut page.content_html,
+            &mut page.content_hash,
+            &mut broken,
+        );
+    }
+    for section in sections.iter_mut() {
+        if let Some(source_path) = &section.source_path {
+            resolve_content(
+                &index,
+                source_path,
+                &mut section.content_html,
+                &mut section.content_hash,
+                &mut broken,
+            );
+        }
+    }
+
+    if broken.is_empty() {
+        return Ok(());
+    }
+
+    let mut report = format!("{} unresolved internal link(s):", broken.len());
+    let mut occurrences: HashMap<(String, String), usize> = HashMap::new();
+    for link in &broken {
+        // Count repeated links so each one points at its own line. Every
+        // occurrence of a broken link is broken, so this counts them all.
+        let nth = occurrences
+            .entry((link.source_path.clone(), link.link.clone()))
+            .and_modify(|n| *n += 1)
+            .or_insert(0);
+        let location = match find_line(&link.source_path, &link.link, *nth) {
+            Some(line) => format!("{}:{line}", link.source_path),
+            None => link.source_path.clone(),
+        };
+        let written = match link.link.strip_prefix(WIKI_LINK_PREFIX) {
+            Some(name) => format!("[[{name}]]"),
+            None => link.link.clone(),
+        };
+        report.push_str(&format!("\n  {location}: {written}: {}", link.reason));
+    }
+    Err(ForgeError::Content(report))
+}
+
+/// Rewrite the internal links of one rendered document. The resolved
+/// targets become part of its content hash, so that the document is
+/// rendered again when a target moves.
+fn resolve_content(
+    index: &LinkIndex,
+    source_path: &str,
+    html: &mut String,
+    content_hash: &mut String,
+    broken: &mut Vec<BrokenLink>,
+) {
+    const HREF: &str = "href=\"@";
+    if !html.contains(HREF) {
+        return;
+    }
+
+    let mut output = String::with_capacity(html.len());
+    let mut resolved = Vec::new();
+    let mut rest = html.as_str();
+
+    while let Some(pos) = rest.find(HREF) {
+        let start = pos + "href=\"".len();
+        output.push_str(&rest[..start]);
+        let value_len = rest[start..].find('"').unwrap_or(rest.len() - start);
+        let raw_href = &rest[start..start + value_len];
+        // The renderer percent-encodes link destinations
+        let href = percent_decode(raw_href);
+        rest = &rest[start + value_len..];
+
+        // Other `@` hrefs, like `@handle`, are left as they are
+        if !href.starts_with(INTERNAL_LINK_PREFIX) && !href.starts_with(WIKI_LINK_PREFIX) {
+            output.push_str(raw_href);
+            continue;
+        }
+
+        match index.resolve(&href) {
+            Ok(url) => {
+                output.push_str(&url);
+                resolved.push(url);
+            }
+            Err(reason) => {
+                output.push_str(raw_href);
+                broken.push(BrokenLink {
+                    source_path: source_path.to_string(),
+                    link: href,
+                    reason,
+                });
+            }
+        }
+    }
+    output.push_str(rest);
+    *html = output;
+
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(content_hash.as_bytes());
+    for url in &resolved {
+        hasher.update(url.as_bytes());
+    }
+    *content_hash = hasher.finalize().to_hex().to_string();
+}
+
+/// File name without extension, or the directory name for `index.md` bundles
+fn file_name(path: &str) -> &str {
+    let path = path.strip_suffix("/index.md").unwrap_or(path);
+    let name = path.rsplit('/').next().unwrap_or(path);
+    name.strip_suffix(".md").unwrap_or(name)
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Values of the `id` attributes in rendered HTML
+fn html_ids(html: &str) -> impl Iterator<Item = String> + '_ {
+    html.split(" id=\"")
+        .skip(1)
+        .filter_map(|rest| rest.split_once('"').map(|(id, _)| id.to_string()))
+}
+
+/// Line of the `nth` occurrence of a link in its source file. The link is
+/// matched as written, anchor included, and only where it ends, so that
+/// `@/a.md` is not found inside `@/a.md#intro`.
+fn find_line(source_path: &str, link: &str, nth: usize) -> Option<usize> {
+    let source = std::fs::read_to_string(source_path).ok()?;
+    let (needle, ends): (String, &[char]) = match link.strip_prefix(WIKI_LINK_PREFIX) {
+        Some(name) => (format!("[[{name}"), &[']', '|']),
+        None => (link.to_string(), &[')', '>', '"', '\'', ']']),
+    };
+    let (offset, _) = source
+        .match_indices(&needle)
+        .filter(|(offset, _)| {
+            source[offset + needle.len()..]
+                .chars()
+                .next()
+                .is_none_or(|c| c.is_whitespace() || ends.contains(&c))
+        })
+        .nth(nth)?;
+    Some(source[..offset].matches('\n').count() + 1)
+}
+
+/// Longest backlink context, in characters
+const CONTEXT_CHARS: usize = 200;
+
+/// The links between posts and pages, for visualization
+#[derive(Debug, Default, Serialize)]
+pub struct LinkGraph {
+    pub nodes: Vec<LinkNode>,
+    pub edges: Vec<LinkEdge>,
+}
+
+/// A post or page in the link graph, identified by its permalink
+#[derive(Debug, Serialize)]
+pub struct LinkNode {
+    pub id: String,
+    pub title: String,
+    pub kind: &'static str,
+    pub section: Option<String>,
+}
+
+/// A link from one post or page to another
+#[derive(Debug, Serialize)]
+pub struct LinkEdge {
+    pub source: String,
+    pub target: String,
+}
+
+/// Find the links between posts and pages in their rendered HTML, fill in
+/// their backlinks and return the whole graph. Call after internal links
+/// are resolved, so that every link is a permalink.
+pub fn build_link_graph(
+    posts: &mut [Post],
+    pages: &mut [Page],
+    sections: &mut [Section],
+) -> LinkGraph {
+    let mut graph = LinkGraph::default();
+    let mut sources = Vec::new();
+
+    for post in posts.iter().chain(sections.iter().flat_map(|s| &s.posts)) {
+        graph.nodes.push(LinkNode {
+            id: post.permalink.clone(),
+            title: post.title.clone(),
+            kind: "post",
+            section: Some(post.section.clone()),
+        });
+        sources.push((PostRef::from(post), post.content_html.as_str()));
+    }
+    for page in pages.iter() {
+        graph.nodes.push(LinkNode {
+            id: page.permalink.clone(),
+            title: page.title.clone(),
+            kind: "page",
+            section: None,
+        });
+        let source = PostRef {
+            title: page.title.clone(),
+            slug: page.slug.clone(),
+            permalink: page.permalink.clone(),
+        };
+        sources.push((source, page.content_html.as_str()));
+    }
+
+    // Links may use the full permalink or only its path
+    let mut targets: HashMap<&str, usize> = HashMap::new();
+    for (i, node) in graph.nodes.iter().enumerate() {
+        targets.insert(node.id.as_str(), i);
+        targets.insert(url_path(&node.id), i);
+    }
+    let mut backlinks: Vec<Vec<Backlink>> = vec![Vec::new(); graph.nodes.len()];
+
+    for (source_index, (source, html)) in sources.iter().enumerate() {
+        let mut linked = HashSet::new();
+        for (pos, href) in hrefs(html) {
+            let url = href.split('#').next().unwrap_or(href);
+            let Some(&target) = targets.get(url) else {
+                continue;
+            };
+            let url = &graph.nodes[target].id;
+            // One backlink per linking document, with the first link's context
+            if target == source_index || !linked.insert(target) {
+                continue;
+            }
+            graph.edges.push(LinkEdge {
+                source: source.permalink.clone(),
+                target: url.clone(),
+            });
+            backlinks[target].push(Backlink {
+                source: source.clone(),
+                context: link_context(html, pos),
+            });
+        }
+    }
+
+    for list in &mut backlinks {
+        list.sort_by(|a, b| a.source.title.cmp(&b.source.title));
+    }
+    let mut backlinks = backlinks.into_iter();
+    for post in posts
+        .iter_mut()
+        .chain(sections.iter_mut().flat_map(|s| s.posts.iter_mut()))
+    {
+        post.backlinks = backlinks.next().unwrap_or_default();
+    }
+    for page in pages.iter_mut() {
+        page.backlinks = backlinks.next().unwrap_or_default();
+    }
+
+    graph
+}
+
+/// Path of an absolute URL, starting at the slash after the host
+fn url_path(url: &str) -> &str {
+    match url.split_once("://") {
+        Some((_, rest)) => rest.find('/').map_or("/", |i| &rest[i..]),
+        None => url,
+    }
+}
+
+/// Byte offset and value of every `href` in rendered HTML
+fn hrefs(html: &str) -> impl Iterator<Item = (usize, &str)> + '_ {
+    html.match_indices("<a href=\"")
+        .filter_map(move |(pos, tag)| {
+            let start = pos + tag.len();
+            let len = html[start..].find('"')?;
+            Some((pos, &html[start..start + len]))
+        })
+}
+
+/// HTML-escaped text of the block containing the link at `pos`, shortened
+/// to about `CONTEXT_CHARS` characters around the link.
+fn link_context(html: &str, pos: usize) -> String {
+    const OPEN: [&str; 7] = ["<p>", "<li>", "<td", "<th", "<dt>", "<dd>", "<h"];
+    const CLOSE: [&str; 7] = ["</p>", "</li>", "</td>", "</th>", "</dt>", "</dd>", "</h"];
+
+    let start = OPEN
+        .iter()
+        .filter_map(|tag| html[..pos].rfind(tag))
+        .max()
+        .unwrap_or(0);
+    let end = CLOSE
+        .iter()
+        .filter_map(|tag| html[pos..].find(tag).map(|i| pos + i))
+        .min()
+        .unwrap_or(html.len());
+
+    let before = plain_text(&html[start..pos]);
+    let after = plain_text(&html[pos..end]);
+    let before: Vec<&str> = before.split_whitespace().collect();
+    let after: Vec<&str> = after.split_whitespace().collect();
+
+    // Keep whole words, with up to a third of the budget before the link
+    let mut words: Vec<&str> = Vec::new();
+    let mut len = 0;
+    for word in before.iter().rev() {
+        if len + word.chars().count() + 1 > CONTEXT_CHARS / 3 {
+            break;
+        }
+        len += word.chars().count() + 1;
+        words.insert(0, word);
+    }
+    let truncated_start = words.len() < before.len();
+    let mut truncated_end = false;
+    for word in &after {
+        if len + word.chars().count() + 1 > CONTEXT_CHARS {
+            truncated_end = true;
+            break;
+        }
+        len += word.chars().count() + 1;
+        words.push(word);
+    }
+
+    let mut context = words.join(" ");
+    if truncated_start {
+        context.insert_str(0, "… ");
+    }
+    if truncated_end {
+        context.push_str(" …");
+    }
+    context
+}
+
+/// Text content of an HTML fragment. Entities are kept, so the text stays
+/// safe to output as HTML.
+fn plain_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text
+}
@@ -1,97 +1,407 @@
-use std::path::Path;
+use chrono::{DateTime, FixedOffset, NaiveDate, Utc};
+use chrono_tz::Tz;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashSet};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use tera::Tera;
 use walkdir::WalkDir;
 
+use crate::config::types::{ImageConfig, MarkdownConfig, SectionConfig, SortBy};
 use crate::config::SiteConfig;
-use crate::content::frontmatter::parse_front_matter;
-use crate::content::markdown::MarkdownRenderer;
+use crate::content::admonition::Admonitions;
+use crate::content::external_links::ExternalLinks;
+use crate::content::frontmatter::{
+    body_line, parse_front_matter, parse_front_matter_as, FrontMatter, FrontMatterDate,
+};
+use crate::content::git::GitHistory;
+use crate::content::highlight::Highlighter;
+use crate::content::markdown::{MarkdownError, MarkdownRenderer, RenderedMarkdown};
 use crate::content::page::Page;
-use crate::content::post::Post;
-use crate::error::ForgeResult;
+use crate::content::permalink;
+use crate::content::post::{Post, TocEntry};
+use crate::content::processors::CodeProcessors;
+use crate::content::section::Section;
+use crate::content::shortcode::{self, Extracted, ShortcodeRenderer};
+use crate::content::transform::EventTransformer;
+use crate::error::{ForgeError, ForgeResult};
+use crate::images::responsive::responsive_images;
+use crate::images::ImageProcessor;
 
-pub struct ContentLoader {
-    renderer: MarkdownRenderer,
+/// Permalink pattern of section entries unless configured otherwise
+const SECTION_PERMALINK: &str = "/:section/:slug/";
+
+const SECTION_INDEX: &str = "_index.md";
+
+pub struct ContentLoader<'a> {
+    renderer: MarkdownRenderer<'a>,
+    markdown: MarkdownConfig,
+    images: &'a ImageProcessor,
+    image_config: ImageConfig,
+    shortcodes: ShortcodeRenderer<'a>,
     base_url: String,
     include_drafts: bool,
+    include_future: bool,
+    generate_toc: bool,
+    toc_levels: RangeInclusive<u32>,
+    math: bool,
+    filename_dates: bool,
+    timezone: Tz,
+    git_history: Option<GitHistory>,
+    /// Reference time for publish and expiry dates, fixed for the whole build
+    now: DateTime<Utc>,
+    posts_per_page: usize,
+    post_permalink: String,
+    sections: BTreeMap<String, SectionConfig>,
 }
 
 #[derive(Debug)]
 pub struct LoadedContent {
     pub posts: Vec<Post>,
     pub pages: Vec<Page>,
+    pub sections: Vec<Section>,
+    pub withheld: Withheld,
+}
+
+/// Content left out of the build because of its publish or expiry date
+#[derive(Debug, Default)]
+pub struct Withheld {
+    /// Dated in the future and waiting to be published
+    pub scheduled: Vec<WithheldItem>,
+    /// Past their expiry date
+    pub expired: Vec<WithheldItem>,
+}
+
+#[derive(Debug)]
+pub struct WithheldItem {
+    pub title: String,
+    pub source_path: String,
+    /// Publish date for scheduled content, expiry date for expired content
+    pub date: DateTime<FixedOffset>,
 }
 
-impl ContentLoader {
-    pub fn new(config: &SiteConfig) -> Self {
+impl<'a> ContentLoader<'a> {
+    pub fn new(
+        config: &SiteConfig,
+        highlighter: &'a Highlighter,
+        images: &'a ImageProcessor,
+        processors: &'a CodeProcessors,
+        git_history: Option<GitHistory>,
+        tera: &'a Tera,
+    ) -> Self {
         Self {
             renderer: MarkdownRenderer::new(
-                &config.build.syntax_theme,
+                highlighter,
                 config.build.syntax_highlighting,
-                config.build.generate_toc,
+                Admonitions::new(&config.admonitions, tera),
+                processors,
+                ExternalLinks::new(config),
             ),
+            markdown: config.markdown.clone(),
+            images,
+            image_config: config.images.clone(),
+            shortcodes: ShortcodeRenderer::new(tera, config),
             base_url: config.base_url.clone(),
             include_drafts: config.build.include_drafts,
+            include_future: config.build.include_future,
+            generate_toc: config.build.generate_toc,
+            toc_levels: config.build.toc_min_level..=config.build.toc_max_level,
+            math: config.build.math,
+            filename_dates: config.build.filename_dates,
+            timezone: config.timezone,
+            git_history,
+            now: Utc::now(),
+            posts_per_page: config.build.posts_per_page,
+            post_permalink: config.build.post_permalink.clone(),
+            sections: config.sections.clone(),
         }
     }
 
+    /// Add a step to the rendering of markdown
+    pub fn add_markdown_transformer(&mut self, transformer: Box<dyn EventTransformer + 'a>) {
+        self.renderer.add_transformer(transformer);
+    }
+
     pub fn load(&self, site_dir: &Path) -> ForgeResult<LoadedContent> {
         let content_dir = site_dir.join(std::path::PathBuf::from("content"));
         let mut posts = Vec::new();
         let mut pages = Vec::new();
+        let mut sections = Vec::new();
+        let mut withheld = Withheld::default();
 
         if !content_dir.exists() {
-            return Ok(LoadedContent { posts, pages });
+            return Ok(LoadedContent {
+                posts,
+                pages,
+                sections,
+                withheld,
+            });
         }
 
         // Load posts
         let posts_dir = content_dir.join("posts");
         if posts_dir.exists() {
-            posts = self.load_posts(&posts_dir)?;
+            posts = self.load_posts(&posts_dir, "posts", &self.post_permalink, &mut withheld)?;
         }
 
         // Load pages
         let pages_dir = content_dir.join("pages");
         if pages_dir.exists() {
-            pages = self.load_pages(&pages_dir)?;
+            pages = self.load_pages(&pages_dir, &mut withheld)?;
+        }
+
+        // Load sections: every other directory that is configured in
+        // forge.toml or has an `_index.md`
+        let mut section_dirs: Vec<PathBuf> = std::fs::read_dir(&content_dir)?
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|path| path.is_dir())
+            .collect();
+        section_dirs.sort();
+
+        for dir in section_dirs {
+            let name = dir.file_name().unwrap_or_default().to_string_lossy();
+            if name == "posts" || name == "pages" {
+                continue;
+            }
+            if self.sections.contains_key(name.as_ref()) || dir.join(SECTION_INDEX).exists() {
+                sections.push(self.load_section(&name, &dir, &mut withheld)?);
+            }
+        }
+
+        for name in self.sections.keys() {
+            if !content_dir.join(name).is_dir() {
+                tracing::warn!("Section '{name}' is configured but content/{name} does not exist");
+            }
         }
 
-        Ok(LoadedContent { posts, pages })
+        Ok(LoadedContent {
+            posts,
+            pages,
+            sections,
+            withheld,
+        })
     }
 
-    fn load_posts(&self, posts_dir: &Path) -> ForgeResult<Vec<Post>> {
+    fn load_section(
+        &self,
+        name: &str,
+        dir: &Path,
+        withheld: &mut Withheld,
+    ) -> ForgeResult<Section> {
+        let index_path = dir.join(SECTION_INDEX);
+        let mut source_path = None;
+        let mut content_hash = String::new();
+        let mut content_html = String::new();
+        let mut index_config = SectionConfig::default();
+
+        if index_path.exists() {
+            let content = std::fs::read_to_string(&index_path)?;
+            let path = index_path.to_string_lossy().to_string();
+            let (fm, body) = parse_front_matter_as::<Option<SectionConfig>>(&content, &path)?;
+            index_config = fm.unwrap_or_default();
+            content_html = self
+                .render_content(&content, &body, &path, &index_config, None, |md| {
+                    self.renderer.render(md, &self.markdown, self.math)
+                })?
+                .html;
+            let mut hasher = blake3::Hasher::new();
+            hasher.update(content.as_bytes());
+            hasher.update(content_html.as_bytes());
+            content_hash = hasher.finalize().to_hex().to_string();
+            source_path = Some(path);
+        }
+
+        // forge.toml settings take precedence over the `_index.md` front matter
+        let config = self
+            .sections
+            .get(name)
+            .cloned()
+            .unwrap_or_default()
+            .or(index_config);
+
+        let slug = slug::slugify(name);
+        let pattern = config.permalink.as_deref().unwrap_or(SECTION_PERMALINK);
+        let mut posts = self.load_posts(dir, name, pattern, withheld)?;
+
+        for post in &mut posts {
+            if post.template.is_none() {
+                post.template = config.page_template.clone();
+            }
+        }
+
+        match config.sort_by.unwrap_or_default() {
+            SortBy::Date => {}
+            SortBy::Title => posts.sort_by_key(|p| p.title.to_lowercase()),
+            SortBy::Weight => posts.sort_by_key(|p| p.weight),
+        }
+
+        Ok(Section {
+            name: name.to_string(),
+            title: config.title.unwrap_or_else(|| title_case(name)),
+            description: config.description.unwrap_or_default(),
+            content_html,
+            permalink: permalink::to_url(&self.base_url, &format!("/{slug}/")),
+            slug,
+            template: config
+                .template
+                .unwrap_or_else(|| "section.html".to_string()),
+            paginate_by: config.paginate_by.unwrap_or(self.posts_per_page),
+            feed: config.feed.unwrap_or(false),
+            posts,
+            source_path,
+            content_hash,
+        })
+    }
+
+    /// Load the posts of a content directory, newest first.
+    fn load_posts(
+        &self,
+        posts_dir: &Path,
+        section: &str,
+        pattern: &str,
+        withheld: &mut Withheld,
+    ) -> ForgeResult<Vec<Post>> {
         let mut posts = Vec::new();
 
+        // Directories holding an `index.md` are page bundles: every other
+        // file inside them is an asset of that post
+        let bundle_dirs: HashSet<PathBuf> = WalkDir::new(posts_dir)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name() == "index.md")
+            .filter_map(|e| e.path().parent().map(Path::to_path_buf))
+            .filter(|dir| dir != posts_dir)
+            .collect();
+
         for entry in WalkDir::new(posts_dir)
             .into_iter()
             .filter_map(|e| e.ok())
             .filter(|e| e.path().extension().is_some_and(|ext| ext == "md"))
         {
             let path = entry.path();
+            if entry.file_name() == SECTION_INDEX {
+                continue;
+            }
+            let bundle_dir = path
+                .parent()
+                .filter(|dir| entry.file_name() == "index.md" && bundle_dirs.contains(*dir));
+
+            // Markdown files inside a bundle are resources, not posts
+            if bundle_dir.is_none()
+                && path
+                    .ancestors()
+                    .skip(1)
+                    .any(|dir| bundle_dirs.contains(dir))
+            {
+                continue;
+            }
+
             let content = std::fs::read_to_string(path)?;
             let source_path = path.to_string_lossy().to_string();
 
-            let (fm, body) = parse_front_matter(&content, &source_path)?;
+            let (mut fm, body) = parse_front_matter(&content, &source_path)?;
+
+            // Bundles are named by their directory, other posts by their file
+            let name = match bundle_dir {
+                Some(dir) => dir.file_name(),
+                None => path.file_stem(),
+            }
+            .unwrap_or_default()
+            .to_string_lossy();
+
+            // Front matter wins over a date prefix, which wins over git
+            // history, which wins over the defaults: the title for the slug
+            // and the build time for the date
+            let name = match split_date_prefix(&name).filter(|_| self.filename_dates) {
+                Some((date, rest)) => {
+                    fm.date.get_or_insert(FrontMatterDate::Local(date.into()));
+                    if fm.slug.is_none() && !rest.is_empty() {
+                        fm.slug = Some(slug::slugify(rest));
+                    }
+                    rest
+                }
+                None => &name,
+            };
+            let git_dates = self.apply_git_dates(&mut fm, path);
 
             if fm.draft && !self.include_drafts {
                 continue;
             }
+            if self.withhold(&fm, &source_path, withheld) {
+                continue;
+            }
+
+            let mut hasher = blake3::Hasher::new();
+            hasher.update(content.as_bytes());
+            hasher.update(git_dates.as_bytes());
 
-            let (html, toc) = self.renderer.render(&body);
+            let assets = match bundle_dir {
+                Some(dir) => {
+                    if fm.slug.is_none() && !name.is_empty() {
+                        fm.slug = Some(slug::slugify(name));
+                    }
+                    let assets = bundle_assets(dir);
+                    for asset in &assets {
+                        hasher.update(asset.as_bytes());
+                        hasher.update(&std::fs::read(dir.join(asset))?);
+                    }
+                    assets
+                }
+                None => Vec::new(),
+            };
 
-            let mut post = Post::from_frontmatter(fm, html, body, toc, source_path, &self.base_url);
+            let toc_levels = self.toc_levels(&fm);
+            let dialect = fm.markdown.apply(&self.markdown);
+            let math = fm.math.unwrap_or(self.math);
+            let mut post = Post::from_frontmatter(
+                fm,
+                body,
+                source_path,
+                section,
+                pattern,
+                &self.base_url,
+                &self.timezone,
+            );
+
+            let rendered = self.render_content(
+                &content,
+                &post.content_raw,
+                &post.source_path,
+                &post,
+                bundle_dir.map(|dir| (post.permalink.as_str(), dir)),
+                |md| {
+                    if bundle_dir.is_some() {
+                        self.renderer
+                            .render_bundle(md, &dialect, math, &post.permalink, &assets)
+                    } else {
+                        self.renderer.render(md, &dialect, math)
+                    }
+                },
+            )?;
+            // Shortcode output depends on templates, not just the source
+            hasher.update(rendered.html.as_bytes());
+            post.content_html = rendered.html;
+            post.toc = toc_levels
+                .map_or_else(Vec::new, |levels| TocEntry::tree(rendered.headings, levels));
+            post.external_links = rendered.external_links;
+            post.bundle_dir = bundle_dir.map(|dir| dir.to_string_lossy().to_string());
+            post.assets = assets;
 
             // Compute content hash
-            post.content_hash = blake3::hash(content.as_bytes()).to_hex().to_string();
+            post.content_hash = hasher.finalize().to_hex().to_string();
 
             posts.push(post);
         }
 
         // Sort by date, newest first
-        posts.sort_by(|a, b| b.date.cmp(&a.date));
+        posts.sort_by_key(|p| std::cmp::Reverse(p.date));
 
         Ok(posts)
     }
 
-    fn load_pages(&self, pages_dir: &Path) -> ForgeResult<Vec<Page>> {
+    fn load_pages(&self, pages_dir: &Path, withheld: &mut Withheld) -> ForgeResult<Vec<Page>> {
         let mut pages = Vec::new();
 
         for entry in WalkDir::new(pages_dir)
@@ -103,18 +413,192 @@ impl ContentLoader {
             let content = std::fs::read_to_string(path)?;
             let source_path = path.to_string_lossy().to_string();
 
-            let (fm, body) = parse_front_matter(&content, &source_path)?;
-
-            let (html, toc) = self.renderer.render(&body);
+            let (mut fm, body) = parse_front_matter(&content, &source_path)?;
+            let git_dates = self.apply_git_dates(&mut fm, path);
+            if self.withhold(&fm, &source_path, withheld) {
+                continue;
+            }
 
-            let mut page =
-                Page::from_frontmatter(fm, html, &body, toc, source_path, &self.base_url);
+            let toc_levels = self.toc_levels(&fm);
+            let dialect = fm.markdown.apply(&self.markdown);
+            let math = fm.math.unwrap_or(self.math);
+            let mut page = Page::from_frontmatter(
+                fm,
+                String::new(),
+                &body,
+                Vec::new(),
+                source_path,
+                &self.base_url,
+                &self.timezone,
+            );
+            let rendered =
+                self.render_content(&content, &body, &page.source_path, &page, None, |md| {
+                    self.renderer.render(md, &dialect, math)
+                })?;
 
-            page.content_hash = blake3::hash(content.as_bytes()).to_hex().to_string();
+            let mut hasher = blake3::Hasher::new();
+            hasher.update(content.as_bytes());
+            hasher.update(git_dates.as_bytes());
+            hasher.update(rendered.html.as_bytes());
+            page.content_html = rendered.html;
+            page.toc = toc_levels
+                .map_or_else(Vec::new, |levels| TocEntry::tree(rendered.headings, levels));
+            page.external_links = rendered.external_links;
+            page.content_hash = hasher.finalize().to_hex().to_string();
 
             pages.push(page);
         }
 
         Ok(pages)
     }
+
+    /// Heading levels in the table of contents of a source file, or `None`
+    /// if it should not have one
+    fn toc_levels(&self, fm: &FrontMatter) -> Option<RangeInclusive<u32>> {
+        if !fm.toc.unwrap_or(self.generate_toc) {
+            return None;
+        }
+        let min = fm.toc_min_level.unwrap_or(*self.toc_levels.start());
+        let max = fm.toc_max_level.unwrap_or(*self.toc_levels.end());
+        Some(min..=max)
+    }
+
+    /// Render the markdown body of a source file, expanding its shortcodes
+    /// and making its images responsive. Shortcode templates see `page` as
+    /// the content being rendered, and shortcode bodies are rendered with
+    /// `render` as well. `bundle` is the URL and directory of a page bundle.
+    fn render_content<P: Serialize>(
+        &self,
+        content: &str,
+        body: &str,
+        source_path: &str,
+        page: &P,
+        bundle: Option<(&str, &Path)>,
+        render: impl Fn(&str) -> Result<RenderedMarkdown, MarkdownError>,
+    ) -> ForgeResult<RenderedMarkdown> {
+        let render_extracted = |extracted: &Extracted| {
+            render(&extracted.markdown).map_err(|e| {
+                ForgeError::Content(format!(
+                    "{source_path}:{}: {}",
+                    extracted.source_line(e.line),
+                    e.message
+                ))
+            })
+        };
+        let extracted = shortcode::extract(body, body_line(content, body), source_path)?;
+        let mut rendered = render_extracted(&extracted)?;
+        if !extracted.calls.is_empty() {
+            // Shortcode bodies can link to other sites too
+            let external_links = RefCell::new(std::mem::take(&mut rendered.external_links));
+            rendered.html = self.shortcodes.insert(
+                &rendered.html,
+                &extracted.calls,
+                page,
+                source_path,
+                &|nested| {
+                    let nested = render_extracted(nested)?;
+                    let mut external_links = external_links.borrow_mut();
+                    for url in nested.external_links {
+                        if !external_links.contains(&url) {
+                            external_links.push(url);
+                        }
+                    }
+                    Ok(nested.html)
+                },
+            )?;
+            rendered.external_links = external_links.into_inner();
+        }
+        if self.image_config.responsive {
+            rendered.html =
+                responsive_images(&rendered.html, self.images, &self.image_config, bundle)
+                    .map_err(|e| ForgeError::Content(format!("{source_path}: {e}")))?;
+        }
+        Ok(rendered)
+    }
+
+    /// Fill in `date` and `updated` from the file's first and last commit
+    /// where front matter omits them. Returns the dates used, so that they
+    /// can be part of the content hash.
+    fn apply_git_dates(&self, fm: &mut FrontMatter, path: &Path) -> String {
+        let Some(dates) = self.git_history.as_ref().and_then(|git| git.dates(path)) else {
+            return String::new();
+        };
+        let created = *fm
+            .date
+            .get_or_insert(FrontMatterDate::Offset(dates.created.fixed_offset()));
+        let updated = *fm
+            .updated
+            .get_or_insert(FrontMatterDate::Offset(dates.updated.fixed_offset()));
+        format!("{created:?} {updated:?}")
+    }
+
+    /// Whether content is left out because it is scheduled for later or has
+    /// expired. Withheld content is recorded for the build summary.
+    fn withhold(&self, fm: &FrontMatter, source_path: &str, withheld: &mut Withheld) -> bool {
+        let item = |date| WithheldItem {
+            title: fm.title.clone(),
+            source_path: source_path.to_string(),
+            date,
+        };
+
+        let in_timezone = |date: &FrontMatterDate| date.in_timezone(&self.timezone);
+
+        if let Some(expiry) = fm.expiry_date.as_ref().map(in_timezone) {
+            if expiry <= self.now {
+                withheld.expired.push(item(expiry));
+                return true;
+            }
+        }
+        if let Some(date) = fm.date.as_ref().map(in_timezone) {
+            if date > self.now && !self.include_future {
+                withheld.scheduled.push(item(date));
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// List the non-markdown files of a page bundle, relative to the bundle directory.
+fn bundle_assets(bundle_dir: &Path) -> Vec<String> {
+    let mut assets: Vec<String> = WalkDir::new(bundle_dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter(|e| e.path().extension().is_none_or(|ext| ext != "md"))
+        .filter_map(|e| {
+            e.path()
+                .strip_prefix(bundle_dir)
+                .ok()
+                .map(|p| p.to_string_lossy().replace('\\', "/"))
+        })
+        .collect();
+    assets.sort();
+    assets
+}
+
+/// Split a Jekyll-style name like "2021-06-14-hello-world" into its date and
+/// the remainder ("hello-world", or empty for a bare date).
+fn split_date_prefix(name: &str) -> Option<(NaiveDate, &str)> {
+    let date = NaiveDate::parse_from_str(name.get(..10)?, "%Y-%m-%d").ok()?;
+    let rest = &name[10..];
+    if rest.is_empty() {
+        return Some((date, rest));
+    }
+    rest.strip_prefix('-').map(|rest| (date, rest))
+}
+
+/// "release-notes" -> "Release Notes"
+fn title_case(name: &str) -> String {
+    name.split(['-', '_'])
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
 }
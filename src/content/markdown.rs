@@ -1,164 +1,156 @@
-use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
-use syntect::highlighting::ThemeSet;
-use syntect::html::highlighted_html_for_string;
-use syntect::parsing::SyntaxSet;
+use std::ops::Range;
 
+use crate::config::types::MarkdownConfig;
+use crate::content::admonition::Admonitions;
+use crate::content::codeblock::CodeBlocks;
+use crate::content::external_links::ExternalLinks;
+use crate::content::highlight::Highlighter;
+use crate::content::math::Math;
 use crate::content::post::TocEntry;
+use crate::content::processors::CodeProcessors;
+use crate::content::transform::{
+    to_html, Autolinks, BundleLinks, EventTransformer, Headings, RenderContext, WikiLinks,
+};
+use pulldown_cmark::{Event, Options, Parser};
 
-pub struct MarkdownRenderer {
-    syntax_set: SyntaxSet,
-    theme_set: ThemeSet,
-    theme_name: String,
-    syntax_highlighting: bool,
-    generate_toc: bool,
+/// A markdown event with its byte range in the source
+pub type SourceEvent<'e> = (Event<'e>, Range<usize>);
+
+/// A problem with a markdown document, at a line counted from its start
+#[derive(Debug)]
+pub struct MarkdownError {
+    pub line: usize,
+    pub message: String,
 }
 
-impl MarkdownRenderer {
-    pub fn new(theme_name: &str, syntax_highlighting: bool, generate_toc: bool) -> Self {
-        Self {
-            syntax_set: SyntaxSet::load_defaults_newlines(),
-            theme_set: ThemeSet::load_defaults(),
-            theme_name: theme_name.to_string(),
-            syntax_highlighting,
-            generate_toc,
-        }
-    }
+/// A rendered markdown document
+#[derive(Debug, Default)]
+pub struct RenderedMarkdown {
+    pub html: String,
+    /// Flat list of the document's headings
+    pub headings: Vec<TocEntry>,
+    /// URLs of links to other sites, in order of first appearance
+    pub external_links: Vec<String>,
+}
 
-    pub fn render(&self, markdown: &str) -> (String, Vec<TocEntry>) {
-        let mut options = Options::empty();
-        options.insert(Options::ENABLE_TABLES);
-        options.insert(Options::ENABLE_FOOTNOTES);
-        options.insert(Options::ENABLE_STRIKETHROUGH);
-        options.insert(Options::ENABLE_TASKLISTS);
+/// Renders markdown through a chain of `EventTransformer`s
+pub struct MarkdownRenderer<'a> {
+    /// Sorted by priority
+    transformers: Vec<Box<dyn EventTransformer + 'a>>,
+}
 
-        let parser = Parser::new_ext(markdown, options);
-        let events: Vec<Event> = parser.collect();
+impl<'a> MarkdownRenderer<'a> {
+    pub fn new(
+        highlighter: &'a Highlighter,
+        syntax_highlighting: bool,
+        admonitions: Admonitions<'a>,
+        processors: &'a CodeProcessors,
+        external_links: ExternalLinks,
+    ) -> Self {
+        let mut renderer = Self {
+            transformers: Vec::new(),
+        };
+        renderer.add_transformer(Box::new(Autolinks));
+        renderer.add_transformer(Box::new(WikiLinks));
+        renderer.add_transformer(Box::new(BundleLinks));
+        renderer.add_transformer(Box::new(processors));
+        renderer.add_transformer(Box::new(CodeBlocks::new(highlighter, syntax_highlighting)));
+        renderer.add_transformer(Box::new(Math));
+        renderer.add_transformer(Box::new(external_links));
+        renderer.add_transformer(Box::new(Headings));
+        renderer.add_transformer(Box::new(admonitions));
+        renderer
+    }
 
-        let mut toc = Vec::new();
-        let mut html_output = String::new();
-        let mut in_code_block = false;
-        let mut code_lang = String::new();
-        let mut code_content = String::new();
-        let mut in_heading = false;
-        let mut heading_level = 0u32;
-        let mut heading_text = String::new();
-        let mut heading_counter: u32 = 0;
+    /// Add a transformer to the chain, after those of the same priority
+    pub fn add_transformer(&mut self, transformer: Box<dyn EventTransformer + 'a>) {
+        self.transformers.push(transformer);
+        self.transformers.sort_by_key(|t| t.priority());
+    }
 
-        for event in events {
-            match event {
-                Event::Start(Tag::CodeBlock(kind)) => {
-                    in_code_block = true;
-                    code_content.clear();
-                    code_lang = match kind {
-                        CodeBlockKind::Fenced(lang) => lang.to_string(),
-                        CodeBlockKind::Indented => String::new(),
-                    };
-                }
-                Event::End(TagEnd::CodeBlock) => {
-                    in_code_block = false;
-                    if self.syntax_highlighting && !code_lang.is_empty() {
-                        let syntax = self
-                            .syntax_set
-                            .find_syntax_by_token(&code_lang)
-                            .unwrap_or_else(|| self.syntax_set.find_syntax_plain_text());
-                        let theme = self
-                            .theme_set
-                            .themes
-                            .get(&self.theme_name)
-                            .unwrap_or_else(|| self.theme_set.themes.values().next().unwrap());
-                        match highlighted_html_for_string(
-                            &code_content,
-                            &self.syntax_set,
-                            syntax,
-                            theme,
-                        ) {
-                            Ok(highlighted) => html_output.push_str(&highlighted),
-                            Err(_) => {
-                                html_output.push_str("<pre><code>");
-                                html_output.push_str(&html_escape(&code_content));
-                                html_output.push_str("</code></pre>");
-                            }
-                        }
-                    } else {
-                        let class = if code_lang.is_empty() {
-                            String::new()
-                        } else {
-                            format!(" class=\"language-{}\"", code_lang)
-                        };
-                        html_output.push_str(&format!("<pre><code{class}>"));
-                        html_output.push_str(&html_escape(&code_content));
-                        html_output.push_str("</code></pre>\n");
-                    }
-                }
-                Event::Start(Tag::Heading { level, .. }) => {
-                    in_heading = true;
-                    heading_text.clear();
-                    heading_level = level as u32;
-                }
-                Event::End(TagEnd::Heading(_)) => {
-                    in_heading = false;
-                    heading_counter += 1;
-                    let id = slug::slugify(&heading_text);
-                    let anchor_id = if id.is_empty() {
-                        format!("heading-{heading_counter}")
-                    } else {
-                        id.clone()
-                    };
+    /// Render markdown to HTML, with `$...$` and `$$...$$` TeX math if
+    /// `math` is set
+    pub fn render(
+        &self,
+        markdown: &str,
+        dialect: &MarkdownConfig,
+        math: bool,
+    ) -> Result<RenderedMarkdown, MarkdownError> {
+        self.render_document(markdown, dialect, math, None)
+    }
 
-                    html_output.push_str(&format!(
-                        "<h{lvl} id=\"{anchor_id}\"><a href=\"#{anchor_id}\" class=\"anchor\">#</a> {text}</h{lvl}>\n",
-                        lvl = heading_level,
-                        text = heading_text,
-                    ));
+    /// Render the markdown of a page bundle, rewriting relative links and
+    /// images that point at the bundle's assets to absolute URLs under
+    /// `bundle_url`, so they also resolve in listings and feeds.
+    pub fn render_bundle(
+        &self,
+        markdown: &str,
+        dialect: &MarkdownConfig,
+        math: bool,
+        bundle_url: &str,
+        assets: &[String],
+    ) -> Result<RenderedMarkdown, MarkdownError> {
+        self.render_document(markdown, dialect, math, Some((bundle_url, assets)))
+    }
 
-                    if self.generate_toc {
-                        toc.push(TocEntry {
-                            level: heading_level,
-                            id: anchor_id,
-                            title: heading_text.clone(),
-                        });
-                    }
-                }
-                Event::Text(text) => {
-                    if in_code_block {
-                        code_content.push_str(&text);
-                    } else if in_heading {
-                        heading_text.push_str(&text);
-                    } else {
-                        html_output.push_str(&text);
-                    }
-                }
-                Event::Code(code) => {
-                    if in_heading {
-                        heading_text.push_str(&format!("<code>{code}</code>"));
-                    } else {
-                        html_output.push_str(&format!("<code>{code}</code>"));
-                    }
-                }
-                Event::SoftBreak => {
-                    if in_heading {
-                        heading_text.push(' ');
-                    } else {
-                        html_output.push('\n');
-                    }
-                }
-                Event::HardBreak => {
-                    html_output.push_str("<br />\n");
-                }
-                _ => {
-                    // For all other events, use pulldown-cmark's HTML rendering
-                    let mut tmp = String::new();
-                    pulldown_cmark::html::push_html(&mut tmp, std::iter::once(event));
-                    html_output.push_str(&tmp);
-                }
-            }
+    fn render_document(
+        &self,
+        markdown: &str,
+        dialect: &MarkdownConfig,
+        math: bool,
+        bundle: Option<(&str, &[String])>,
+    ) -> Result<RenderedMarkdown, MarkdownError> {
+        let mut context = RenderContext {
+            markdown,
+            dialect,
+            bundle,
+            headings: Vec::new(),
+            external_links: Vec::new(),
+        };
+        let mut options = self.options(dialect);
+        // Math events only occur when parsed, so the math transformer is
+        // idle otherwise
+        options.set(Options::ENABLE_MATH, math);
+        let mut events: Vec<SourceEvent> = Parser::new_ext(markdown, options)
+            .into_offset_iter()
+            .collect();
+        for transformer in &self.transformers {
+            events = transformer.transform(events, &mut context)?;
         }
 
-        (html_output, toc)
+        Ok(RenderedMarkdown {
+            html: to_html(events),
+            headings: context.headings,
+            external_links: context.external_links,
+        })
+    }
+
+    /// Parser options for a dialect. GFM autolinks are not a parser
+    /// option; the `Autolinks` transformer adds them.
+    fn options(&self, dialect: &MarkdownConfig) -> Options {
+        let mut options = Options::empty();
+        for (enabled, option) in [
+            (dialect.tables, Options::ENABLE_TABLES),
+            (dialect.footnotes, Options::ENABLE_FOOTNOTES),
+            (dialect.strikethrough, Options::ENABLE_STRIKETHROUGH),
+            (dialect.tasklists, Options::ENABLE_TASKLISTS),
+            (dialect.smart_punctuation, Options::ENABLE_SMART_PUNCTUATION),
+            (
+                dialect.heading_attributes,
+                Options::ENABLE_HEADING_ATTRIBUTES,
+            ),
+            (dialect.definition_lists, Options::ENABLE_DEFINITION_LIST),
+            (dialect.superscript, Options::ENABLE_SUPERSCRIPT),
+            (dialect.subscript, Options::ENABLE_SUBSCRIPT),
+            (dialect.wiki_links, Options::ENABLE_WIKILINKS),
+        ] {
+            options.set(option, enabled);
+        }
+        options
     }
 }
 
-fn html_escape(s: &str) -> String {
+pub(crate) fn html_escape(s: &str) -> String {
     s.replace('&', "&amp;")
         .replace('<', "&lt;")
         .replace('>', "&gt;")
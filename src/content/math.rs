@@ -0,0 +1,74 @@
+use std::error::Error;
+
+use pulldown_cmark::Event;
+use pulldown_latex::config::DisplayMode;
+use pulldown_latex::{push_mathml, Parser, ParserError, RenderConfig, Storage};
+
+use crate::content::markdown::{MarkdownError, SourceEvent};
+use crate::content::transform::{EventTransformer, RenderContext};
+
+/// Render TeX math as MathML, keeping the TeX as an annotation. Malformed
+/// TeX is an error rather than red text in the output.
+pub fn render_math(tex: &str, display: bool) -> Result<String, String> {
+    let storage = Storage::new();
+    let events = Parser::new(tex, &storage)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| {
+            // The error itself carries a multi-line excerpt; its source is
+            // the short description
+            let reason = e.source().map_or_else(|| e.to_string(), |r| r.to_string());
+            format!("invalid TeX `{}`: {reason}", tex.trim())
+        })?;
+
+    let config = RenderConfig {
+        display_mode: if display {
+            DisplayMode::Block
+        } else {
+            DisplayMode::Inline
+        },
+        annotation: Some(tex.trim()),
+        ..RenderConfig::default()
+    };
+    let mut mathml = String::new();
+    push_mathml(
+        &mut mathml,
+        events.into_iter().map(Ok::<_, ParserError>),
+        config,
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(mathml)
+}
+
+/// Renders `$...$` and `$$...$$` math as MathML
+pub struct Math;
+
+impl EventTransformer for Math {
+    fn name(&self) -> &str {
+        "math"
+    }
+
+    fn priority(&self) -> i32 {
+        50
+    }
+
+    fn transform<'e>(
+        &self,
+        events: Vec<SourceEvent<'e>>,
+        context: &mut RenderContext,
+    ) -> Result<Vec<SourceEvent<'e>>, MarkdownError> {
+        events
+            .into_iter()
+            .map(|(event, range)| {
+                let (tex, display) = match &event {
+                    Event::InlineMath(tex) => (tex, false),
+                    Event::DisplayMath(tex) => (tex, true),
+                    _ => return Ok((event, range)),
+                };
+                match render_math(tex, display) {
+                    Ok(mathml) => Ok((Event::InlineHtml(mathml.into()), range)),
+                    Err(message) => Err(context.error(&range, message)),
+                }
+            })
+            .collect()
+    }
+}
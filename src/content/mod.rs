@@ -1,5 +1,17 @@
+pub mod admonition;
+pub mod codeblock;
+pub mod external_links;
 pub mod frontmatter;
+pub mod git;
+pub mod highlight;
+pub mod links;
 pub mod loader;
 pub mod markdown;
+pub mod math;
 pub mod page;
+pub mod permalink;
 pub mod post;
+pub mod processors;
+pub mod section;
+pub mod shortcode;
+pub mod transform;
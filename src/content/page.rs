@@ -1,8 +1,12 @@
+use chrono::{DateTime, FixedOffset};
+use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 use crate::content::frontmatter::FrontMatter;
+use crate::content::permalink;
 use crate::content::post::TocEntry;
+use crate::types::Backlink;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Page {
@@ -11,11 +15,19 @@ pub struct Page {
     pub content_html: String,
     pub toc: Vec<TocEntry>,
     pub permalink: String,
+    /// Old URL paths redirecting to this permalink
+    pub aliases: Vec<String>,
     pub template: Option<String>,
+    /// Posts and pages linking here
+    pub backlinks: Vec<Backlink>,
+    /// Date of the last significant change, if known
+    pub updated: Option<DateTime<FixedOffset>>,
     pub word_count: usize,
     pub reading_time: usize,
     pub source_path: String,
     pub content_hash: String,
+    /// URLs of the links to other sites in the content
+    pub external_links: Vec<String>,
     pub extra: HashMap<String, serde_json::Value>,
 }
 
@@ -27,11 +39,16 @@ impl Page {
         toc: Vec<TocEntry>,
         source_path: String,
         base_url: &str,
+        timezone: &Tz,
     ) -> Self {
         let slug = fm.slug.unwrap_or_else(|| slug::slugify(&fm.title));
         let word_count = content_raw.split_whitespace().count();
         let reading_time = std::cmp::max(1, (word_count as f64 / 200.0).ceil() as usize);
-        let permalink = format!("{}/{}/", base_url.trim_end_matches('/'), slug);
+        let path = match &fm.permalink {
+            Some(path) => permalink::normalize(path),
+            None => format!("/{slug}/"),
+        };
+        let permalink = permalink::to_url(base_url, &path);
 
         Self {
             title: fm.title,
@@ -39,11 +56,15 @@ impl Page {
             content_html,
             toc,
             permalink,
+            aliases: fm.aliases,
             template: fm.template,
+            backlinks: Vec::new(),
+            updated: fm.updated.map(|date| date.in_timezone(timezone)),
             word_count,
             reading_time,
             source_path,
             content_hash: String::new(),
+            external_links: Vec::new(),
             extra: fm.extra,
         }
     }
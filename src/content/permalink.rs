@@ -0,0 +1,69 @@
+use std::path::{Component, Path};
+
+use chrono::Datelike;
+
+use crate::content::post::Post;
+
+/// Expand a permalink pattern such as `/:year/:month/:slug/` into a URL
+/// path for a post. `:category` uses the post's first category.
+pub fn expand(pattern: &str, post: &Post) -> String {
+    let category = post
+        .categories
+        .first()
+        .map(slug::slugify)
+        .unwrap_or_else(|| "uncategorized".to_string());
+
+    let path = pattern
+        .replace(":year", &format!("{:04}", post.date.year()))
+        .replace(":month", &format!("{:02}", post.date.month()))
+        .replace(":day", &format!("{:02}", post.date.day()))
+        .replace(":section", &slug::slugify(&post.section))
+        .replace(":category", &category)
+        .replace(":slug", &post.slug)
+        .replace(":title", &slug::slugify(&post.title));
+    normalize(&path)
+}
+
+/// Ensure a URL path has exactly one leading and trailing slash.
+pub fn normalize(path: &str) -> String {
+    let trimmed = path.trim_matches('/');
+    if trimmed.is_empty() {
+        "/".to_string()
+    } else {
+        format!("/{trimmed}/")
+    }
+}
+
+/// Check that a URL path or pattern from front matter stays inside the
+/// output directory: every segment must be a plain name, not `..`, `.` or
+/// anything else the filesystem treats specially.
+pub fn check_path(path: &str) -> Result<(), String> {
+    for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+        let normal = !segment.contains('\\')
+            && Path::new(segment)
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)));
+        if !normal {
+            return Err(format!(
+                "\"{path}\" contains \"{segment}\", but paths must stay inside the output directory"
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Join a URL path onto the site's base URL.
+pub fn to_url(base_url: &str, path: &str) -> String {
+    format!(
+        "{}/{}",
+        base_url.trim_end_matches('/'),
+        path.trim_start_matches('/')
+    )
+}
+
+/// The URL path of a permalink, relative to the site's base URL.
+pub fn url_path<'a>(base_url: &str, permalink: &'a str) -> &'a str {
+    permalink
+        .strip_prefix(base_url.trim_end_matches('/'))
+        .unwrap_or(permalink)
+}
@@ -1,14 +1,22 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, FixedOffset, Utc};
+use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::iter::Peekable;
+use std::ops::RangeInclusive;
 
 use crate::content::frontmatter::FrontMatter;
+use crate::content::permalink;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Post {
     pub title: String,
     pub slug: String,
-    pub date: DateTime<Utc>,
+    /// Publish date in the site timezone
+    pub date: DateTime<FixedOffset>,
+    /// Date of the last significant change, if known
+    pub updated: Option<DateTime<FixedOffset>>,
+    pub expiry_date: Option<DateTime<FixedOffset>>,
     pub draft: bool,
     pub description: String,
     pub summary: String,
@@ -20,11 +28,24 @@ pub struct Post {
     pub categories: Vec<String>,
     pub tags: Vec<String>,
     pub permalink: String,
+    /// Old URL paths redirecting to this permalink
+    pub aliases: Vec<String>,
     pub template: Option<String>,
+    /// Content section the post belongs to ("posts" for regular posts)
+    pub section: String,
+    pub weight: i32,
     pub earlier: Option<crate::types::PostRef>,
     pub later: Option<crate::types::PostRef>,
+    /// Posts and pages linking here
+    pub backlinks: Vec<crate::types::Backlink>,
     pub content_hash: String,
     pub source_path: String,
+    /// Source directory of a page bundle (`posts/my-post/index.md`)
+    pub bundle_dir: Option<String>,
+    /// Files co-located with a bundle's `index.md`, relative to the bundle
+    pub assets: Vec<String>,
+    /// URLs of the links to other sites in the content
+    pub external_links: Vec<String>,
     pub extra: HashMap<String, serde_json::Value>,
 }
 
@@ -33,49 +54,99 @@ pub struct TocEntry {
     pub level: u32,
     pub id: String,
     pub title: String,
+    /// Deeper headings up to the next heading of this level or above
+    #[serde(default)]
+    pub children: Vec<TocEntry>,
+}
+
+impl TocEntry {
+    /// Nest a flat list of headings into a tree, keeping the levels in
+    /// `levels`. A heading that skips levels becomes a child of the closest
+    /// shallower heading before it.
+    pub fn tree(headings: Vec<TocEntry>, levels: RangeInclusive<u32>) -> Vec<TocEntry> {
+        let mut headings = headings
+            .into_iter()
+            .filter(|entry| levels.contains(&entry.level))
+            .peekable();
+        nest(&mut headings, 0)
+    }
+}
+
+fn nest(
+    headings: &mut Peekable<impl Iterator<Item = TocEntry>>,
+    parent_level: u32,
+) -> Vec<TocEntry> {
+    let mut entries = Vec::new();
+    while let Some(mut entry) = headings.next_if(|entry| entry.level > parent_level) {
+        entry.children = nest(headings, entry.level);
+        entries.push(entry);
+    }
+    entries
 }
 
 impl Post {
+    /// When the post last changed: `updated` if known, otherwise `date`
+    pub fn last_modified(&self) -> DateTime<FixedOffset> {
+        self.updated.unwrap_or(self.date)
+    }
+
+    /// A post of `section`, with its permalink expanded from front matter's
+    /// `permalink` or else `pattern`. The content is rendered later.
     pub fn from_frontmatter(
         fm: FrontMatter,
-        content_html: String,
         content_raw: String,
-        toc: Vec<TocEntry>,
         source_path: String,
+        section: &str,
+        pattern: &str,
         base_url: &str,
+        timezone: &Tz,
     ) -> Self {
         let slug = fm.slug.unwrap_or_else(|| slug::slugify(&fm.title));
         let word_count = content_raw.split_whitespace().count();
         let reading_time = (word_count as f64 / 200.0).ceil() as usize;
         let reading_time = if reading_time == 0 { 1 } else { reading_time };
-        let permalink = format!("{}/posts/{}/", base_url.trim_end_matches('/'), slug);
 
         let summary = fm
             .summary
             .clone()
             .unwrap_or_else(|| fm.description.clone().unwrap_or_default());
 
-        Self {
+        let pattern = fm.permalink.unwrap_or_else(|| pattern.to_string());
+        let mut post = Self {
             title: fm.title,
             slug,
-            date: fm.date.unwrap_or_else(Utc::now),
+            date: fm.date.map_or_else(
+                || Utc::now().with_timezone(timezone).fixed_offset(),
+                |date| date.in_timezone(timezone),
+            ),
+            updated: fm.updated.map(|date| date.in_timezone(timezone)),
+            expiry_date: fm.expiry_date.map(|date| date.in_timezone(timezone)),
             draft: fm.draft,
             description: fm.description.unwrap_or_default(),
             summary,
             content_raw,
-            content_html,
-            toc,
+            content_html: String::new(),
+            toc: Vec::new(),
             word_count,
             reading_time,
             categories: fm.categories,
             tags: fm.tags,
-            permalink,
+            permalink: String::new(),
+            aliases: fm.aliases,
             template: fm.template,
+            section: section.to_string(),
+            weight: fm.weight,
             earlier: None,
             later: None,
+            backlinks: Vec::new(),
             content_hash: String::new(),
             source_path,
+            bundle_dir: None,
+            assets: Vec::new(),
+            external_links: Vec::new(),
             extra: fm.extra,
-        }
+        };
+        post.permalink = permalink::to_url(base_url, &permalink::expand(&pattern, &post));
+        post
     }
 }
@@ -0,0 +1,165 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::config::types::{BuiltinProcessor, CodeProcessorConfig};
+use crate::config::SiteConfig;
+use pulldown_cmark::{CodeBlockKind, Event, Tag};
+
+use crate::content::codeblock::{code_block, CodeBlockInfo};
+use crate::content::markdown::{html_escape, MarkdownError, SourceEvent};
+use crate::content::transform::{html_event, replace_elements, EventTransformer, RenderContext};
+
+/// Directory of processed code blocks in `.forge_cache`
+const CACHE_DIR: &str = "code_processors";
+
+/// Replaces fenced code blocks of configured languages with the output of
+/// a command or a built-in renderer. Command output is cached in
+/// `.forge_cache/code_processors/` by a hash of the command and the block,
+/// so commands only run for new or changed blocks.
+pub struct CodeProcessors {
+    processors: BTreeMap<String, CodeProcessorConfig>,
+    site_dir: PathBuf,
+    cache_dir: PathBuf,
+}
+
+impl CodeProcessors {
+    pub fn new(site_dir: &Path, config: &SiteConfig) -> Self {
+        Self {
+            processors: config.code_processors.clone(),
+            site_dir: site_dir.to_path_buf(),
+            cache_dir: site_dir.join(".forge_cache").join(CACHE_DIR),
+        }
+    }
+
+    /// Whether blocks with an info string are processed
+    fn handles(&self, info: &str) -> bool {
+        self.processors
+            .contains_key(&CodeBlockInfo::parse(info).lang)
+    }
+
+    /// Render a code block in a handled language as a `<figure>` of the
+    /// processor's output, captioned with the block's title
+    fn render(&self, block: &CodeBlockInfo, code: &str) -> Result<String, String> {
+        let lang = html_escape(&block.lang);
+        let mut html = format!("<figure class=\"processed-block\" data-lang=\"{lang}\">\n");
+        if let Some(title) = &block.title {
+            html.push_str(&format!(
+                "<figcaption>{}</figcaption>\n",
+                html_escape(title)
+            ));
+        }
+        let output = self
+            .process(&block.lang, code)
+            .map_err(|e| format!("{} block: {e}", block.lang))?;
+        html.push_str(output.trim_end());
+        html.push_str("\n</figure>\n");
+        Ok(html)
+    }
+
+    fn process(&self, lang: &str, code: &str) -> Result<String, String> {
+        let Some(processor) = self.processors.get(lang) else {
+            return Err(format!("no processor for {lang} blocks"));
+        };
+        match processor.builtin {
+            Some(BuiltinProcessor::Raw) => return Ok(code.to_string()),
+            Some(BuiltinProcessor::Svgbob) => return Ok(svgbob::to_svg(code)),
+            None => {}
+        }
+
+        let key = blake3::hash(format!("{}\0{code}", processor.command.join("\0")).as_bytes());
+        let cached = self.cache_dir.join(format!("{}.html", key.to_hex()));
+        if let Ok(output) = fs::read_to_string(&cached) {
+            return Ok(output);
+        }
+
+        let output = strip_xml_prolog(&self.run(&processor.command, code)?).to_string();
+        fs::create_dir_all(&self.cache_dir)
+            .and_then(|_| fs::write(&cached, &output))
+            .map_err(|e| format!("could not cache {lang} output: {e}"))?;
+        Ok(output)
+    }
+
+    /// Run a command in the site directory with `input` on stdin
+    fn run(&self, command: &[String], input: &str) -> Result<String, String> {
+        let program = &command[0];
+        let mut child = Command::new(program)
+            .args(&command[1..])
+            .current_dir(&self.site_dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("could not run `{program}`: {e}"))?;
+
+        // Write from another thread, so a command that prints before it has
+        // read all its input cannot block on a full pipe
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let input = input.to_string();
+        let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+        let output = child
+            .wait_with_output()
+            .map_err(|e| format!("could not run `{program}`: {e}"))?;
+        // A command may exit without reading its input; its status tells
+        let _ = writer.join();
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!(
+                "`{}` failed ({}): {}",
+                command.join(" "),
+                output.status,
+                stderr.trim()
+            ));
+        }
+        String::from_utf8(output.stdout).map_err(|_| format!("`{program}` printed invalid UTF-8"))
+    }
+}
+
+impl EventTransformer for CodeProcessors {
+    fn name(&self) -> &str {
+        "code_processors"
+    }
+
+    fn priority(&self) -> i32 {
+        30
+    }
+
+    fn transform<'e>(
+        &self,
+        events: Vec<SourceEvent<'e>>,
+        context: &mut RenderContext,
+    ) -> Result<Vec<SourceEvent<'e>>, MarkdownError> {
+        if self.processors.is_empty() {
+            return Ok(events);
+        }
+        replace_elements(
+            events,
+            |event| match event {
+                Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) => self.handles(info),
+                _ => false,
+            },
+            |block| {
+                let (info, code, range) = code_block(block);
+                match self.render(&info, &code) {
+                    Ok(html) => Ok(vec![html_event(html, range)]),
+                    Err(message) => Err(context.error(&range, message)),
+                }
+            },
+        )
+    }
+}
+
+/// SVG files start with an XML declaration and doctype that do not belong
+/// in HTML
+fn strip_xml_prolog(output: &str) -> &str {
+    let trimmed = output.trim_start();
+    if trimmed.starts_with("<?xml") || trimmed.starts_with("<!DOCTYPE") {
+        if let Some(start) = trimmed.find("<svg") {
+            return &trimmed[start..];
+        }
+    }
+    output
+}
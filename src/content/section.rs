@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+
+use crate::content::post::Post;
+
+/// A content section (`content/<name>/`) with its own listing, templates,
+/// ordering and permalinks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Section {
+    pub name: String,
+    pub slug: String,
+    pub title: String,
+    pub description: String,
+    /// Rendered body of the section's `_index.md`
+    pub content_html: String,
+    pub permalink: String,
+    pub template: String,
+    pub paginate_by: usize,
+    pub feed: bool,
+    pub posts: Vec<Post>,
+    pub source_path: Option<String>,
+    pub content_hash: String,
+}
+
+/// Summary of a section, available in every template context
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SectionRef {
+    pub name: String,
+    pub title: String,
+    pub permalink: String,
+    pub post_count: usize,
+}
+
+impl From<&Section> for SectionRef {
+    fn from(section: &Section) -> Self {
+        Self {
+            name: section.name.clone(),
+            title: section.title.clone(),
+            permalink: section.permalink.clone(),
+            post_count: section.posts.len(),
+        }
+    }
+}
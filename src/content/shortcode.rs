@@ -0,0 +1,491 @@
+use serde::Serialize;
+use tera::{Context, Map, Tera, Value};
+
+use crate::error::{ForgeError, ForgeResult};
+
+/// Directory below the template roots that holds shortcode templates
+const SHORTCODE_DIR: &str = "shortcodes";
+
+/// A shortcode call found in markdown: `{{ name(args) }}` inline or
+/// `{% name(args) %}body{% end %}` with a body.
+#[derive(Debug, Clone)]
+pub struct ShortcodeCall {
+    pub name: String,
+    pub args: Map<String, Value>,
+    pub body: Option<String>,
+    /// Line of the call in the source file
+    pub line: usize,
+    /// Line of the first body line in the source file
+    body_line: usize,
+}
+
+/// Markdown with its shortcode calls replaced by placeholders
+#[derive(Debug, Default)]
+pub struct Extracted {
+    pub markdown: String,
+    pub calls: Vec<ShortcodeCall>,
+    /// Lines of `markdown` paired with their line in the source file, from
+    /// the start and after every call that spanned several lines
+    line_starts: Vec<(usize, usize)>,
+}
+
+impl Extracted {
+    /// Line in the source file of a line of `markdown`, both counted from 1
+    pub fn source_line(&self, line: usize) -> usize {
+        let (start, source) = self
+            .line_starts
+            .iter()
+            .rev()
+            .find(|(start, _)| *start <= line)
+            .copied()
+            .unwrap_or((1, 1));
+        source + line - start
+    }
+}
+
+/// Renders shortcode calls through the site's Tera engine, where they are
+/// `shortcodes/<name>.html` templates from the theme or the site.
+pub struct ShortcodeRenderer<'a> {
+    tera: &'a Tera,
+    config: Value,
+}
+
+impl<'a> ShortcodeRenderer<'a> {
+    pub fn new<C: Serialize>(tera: &'a Tera, config: &C) -> Self {
+        Self {
+            tera,
+            config: serde_json::to_value(config).unwrap_or_default(),
+        }
+    }
+
+    /// Replace the placeholders in rendered HTML with the output of their
+    /// shortcodes. A placeholder that makes up a whole paragraph replaces
+    /// the paragraph. Bodies are rendered as markdown with `render_markdown`.
+    pub fn insert<P: Serialize>(
+        &self,
+        html: &str,
+        calls: &[ShortcodeCall],
+        page: &P,
+        source_path: &str,
+        render_markdown: &dyn Fn(&Extracted) -> ForgeResult<String>,
+    ) -> ForgeResult<String> {
+        let page = serde_json::to_value(page).unwrap_or_default();
+        self.insert_value(html, calls, &page, source_path, render_markdown)
+    }
+
+    fn insert_value(
+        &self,
+        html: &str,
+        calls: &[ShortcodeCall],
+        page: &Value,
+        source_path: &str,
+        render_markdown: &dyn Fn(&Extracted) -> ForgeResult<String>,
+    ) -> ForgeResult<String> {
+        let mut html = html.to_string();
+
+        for (index, call) in calls.iter().enumerate() {
+            let output = self.render_call(call, page, source_path, render_markdown)?;
+            // Template files end with a newline that would add a space inline
+            let output = output.trim_end();
+            let marker = placeholder(index);
+            let paragraph = format!("<p>{marker}</p>");
+            html = if html.contains(&paragraph) {
+                html.replacen(&paragraph, output, 1)
+            } else {
+                html.replacen(&marker, output, 1)
+            };
+        }
+
+        Ok(html)
+    }
+
+    fn render_call(
+        &self,
+        call: &ShortcodeCall,
+        page: &Value,
+        source_path: &str,
+        render_markdown: &dyn Fn(&Extracted) -> ForgeResult<String>,
+    ) -> ForgeResult<String> {
+        let error = |message: String| {
+            ForgeError::Content(format!(
+                "{source_path}:{}: shortcode '{}': {message}",
+                call.line, call.name
+            ))
+        };
+
+        let template = format!("{SHORTCODE_DIR}/{}.html", call.name);
+        if !self.tera.get_template_names().any(|name| name == template) {
+            return Err(error(format!("no template {template} found")));
+        }
+
+        let mut context = Context::new();
+        for (key, value) in &call.args {
+            context.insert(key, value);
+        }
+        if let Some(body) = &call.body {
+            // Bodies may use shortcodes themselves
+            let nested = extract(body, call.body_line, source_path)?;
+            let body_html = render_markdown(&nested)?;
+            let body_html = self.insert_value(
+                &body_html,
+                &nested.calls,
+                page,
+                source_path,
+                render_markdown,
+            )?;
+            context.insert("body", &body_html);
+        }
+        context.insert("page", page);
+        context.insert("config", &self.config);
+
+        self.tera
+            .render(&template, &context)
+            .map_err(|e| error(error_chain(&e)))
+    }
+}
+
+/// Placeholder standing in for a shortcode while the markdown is rendered.
+/// It is plain text that markdown leaves untouched.
+fn placeholder(index: usize) -> String {
+    format!("forgeshortcode{index}placeholder")
+}
+
+/// Tera reports the interesting part of an error in its sources
+pub(crate) fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}
+
+/// Replace shortcode calls in markdown with placeholders, leaving fenced
+/// code blocks and code spans alone. `{{/* name() */}}` and
+/// `{%/* name() */%}` produce the call literally. `first_line` is the line of
+/// the markdown's first line in the source file, for error messages.
+pub fn extract(markdown: &str, first_line: usize, source_path: &str) -> ForgeResult<Extracted> {
+    let mut extracted = Extracted {
+        line_starts: vec![(1, first_line)],
+        ..Extracted::default()
+    };
+    let out = &mut extracted.markdown;
+    let mut rest = markdown;
+    let mut fence: Option<(char, usize)> = None;
+    let mut at_line_start = true;
+
+    let line_of = |rest: &str| {
+        first_line
+            + markdown[..markdown.len() - rest.len()]
+                .matches('\n')
+                .count()
+    };
+    let error = |rest: &str, message: &str| {
+        ForgeError::Content(format!("{source_path}:{}: {message}", line_of(rest)))
+    };
+
+    while let Some(c) = rest.chars().next() {
+        if at_line_start {
+            at_line_start = false;
+            let line_end = rest.find('\n').map_or(rest.len(), |pos| pos + 1);
+            let line = &rest[..line_end];
+            let marker = fence_marker(line);
+
+            match (fence, marker) {
+                (Some((fc, flen)), Some((mc, mlen))) if fc == mc && mlen >= flen => {
+                    fence = None;
+                }
+                (None, Some(marker)) => fence = Some(marker),
+                _ => {}
+            }
+            if fence.is_some() || marker.is_some() {
+                out.push_str(line);
+                rest = &rest[line_end..];
+                at_line_start = true;
+                continue;
+            }
+        }
+
+        if c == '\n' {
+            out.push(c);
+            rest = &rest[1..];
+            at_line_start = true;
+            continue;
+        }
+
+        // Code spans are copied verbatim
+        if c == '`' {
+            let run = rest.len() - rest.trim_start_matches('`').len();
+            let ticks = &rest[..run];
+            let after = &rest[run..];
+            match find_closing_ticks(after, run) {
+                Some(end) => {
+                    let span_len = run + end + run;
+                    out.push_str(&rest[..span_len]);
+                    rest = &rest[span_len..];
+                }
+                None => {
+                    out.push_str(ticks);
+                    rest = after;
+                }
+            }
+            continue;
+        }
+
+        // Escaped calls
+        if let Some(inner) = rest.strip_prefix("{{/*") {
+            if let Some(end) = inner.find("*/}}") {
+                out.push_str("{{");
+                out.push_str(&inner[..end]);
+                out.push_str("}}");
+                rest = &inner[end + 4..];
+                continue;
+            }
+        }
+        if let Some(inner) = rest.strip_prefix("{%/*") {
+            if let Some(end) = inner.find("*/%}") {
+                out.push_str("{%");
+                out.push_str(&inner[..end]);
+                out.push_str("%}");
+                rest = &inner[end + 4..];
+                continue;
+            }
+        }
+
+        // Inline call
+        if let Some(inner) = rest.strip_prefix("{{") {
+            if let Some((name, after_name)) = call_name(inner) {
+                let (args, after_args) =
+                    parse_args(after_name).map_err(|message| error(rest, &message))?;
+                let Some(after) = after_args.trim_start().strip_prefix("}}") else {
+                    return Err(error(rest, &format!("expected }}}} after {name}(...)")));
+                };
+                extracted.calls.push(ShortcodeCall {
+                    name: name.to_string(),
+                    args,
+                    body: None,
+                    line: line_of(rest),
+                    body_line: line_of(rest),
+                });
+                out.push_str(&placeholder(extracted.calls.len() - 1));
+                note_line_shift(out, &mut extracted.line_starts, line_of(after));
+                rest = after;
+                continue;
+            }
+        }
+
+        // Call with a body
+        if let Some(inner) = rest.strip_prefix("{%") {
+            if let Some((name, after_name)) = call_name(inner) {
+                let (args, after_args) =
+                    parse_args(after_name).map_err(|message| error(rest, &message))?;
+                let Some(body_start) = after_args.trim_start().strip_prefix("%}") else {
+                    return Err(error(rest, &format!("expected %}} after {name}(...)")));
+                };
+                let Some((body, after)) = split_body(body_start) else {
+                    return Err(error(
+                        rest,
+                        &format!("{name}(...) has no matching {{% end %}}"),
+                    ));
+                };
+                // A body starting on the line after the call starts there
+                let body_text = body.strip_prefix('\n').unwrap_or(body);
+                let body_line = line_of(&body_start[body.len() - body_text.len()..]);
+                extracted.calls.push(ShortcodeCall {
+                    name: name.to_string(),
+                    args,
+                    body: Some(body_text.to_string()),
+                    line: line_of(rest),
+                    body_line,
+                });
+                out.push_str(&placeholder(extracted.calls.len() - 1));
+                note_line_shift(out, &mut extracted.line_starts, line_of(after));
+                rest = after;
+                continue;
+            }
+        }
+
+        out.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+
+    Ok(extracted)
+}
+
+/// Record where the lines of extracted markdown continue in the source
+/// after a call that ended on `source_line`, if the call spanned lines
+fn note_line_shift(out: &str, line_starts: &mut Vec<(usize, usize)>, source_line: usize) {
+    let line = out.matches('\n').count() + 1;
+    let (start, source) = line_starts[line_starts.len() - 1];
+    if source + line - start != source_line {
+        // The rest of the placeholder's line is rare enough to be ignored
+        line_starts.push((line + 1, source_line + 1));
+    }
+}
+
+/// The fence character and length if the line opens or closes a fenced code block
+fn fence_marker(line: &str) -> Option<(char, usize)> {
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    if indent > 3 {
+        return None;
+    }
+    let line = &line[indent..];
+    let c = line.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let len = line.len() - line.trim_start_matches(c).len();
+    (len >= 3).then_some((c, len))
+}
+
+/// Offset of a run of exactly `len` backticks closing a code span
+fn find_closing_ticks(text: &str, len: usize) -> Option<usize> {
+    let mut offset = 0;
+    while let Some(pos) = text[offset..].find('`') {
+        let start = offset + pos;
+        let run = text[start..].len() - text[start..].trim_start_matches('`').len();
+        if run == len {
+            return Some(start);
+        }
+        offset = start + run;
+    }
+    None
+}
+
+/// `name(` at the start of a tag, with the name and the text after `(`
+fn call_name(tag: &str) -> Option<(&str, &str)> {
+    let tag = tag.trim_start();
+    let end = tag
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(tag.len());
+    let name = &tag[..end];
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) || name == "end" {
+        return None;
+    }
+    let after = tag[end..].strip_prefix('(')?;
+    Some((name, after))
+}
+
+/// Split a body at its `{% end %}`, allowing nested calls with bodies.
+/// Returns the body and the text after the closing tag.
+fn split_body(text: &str) -> Option<(&str, &str)> {
+    let mut depth = 0;
+    let mut offset = 0;
+
+    while let Some(pos) = text[offset..].find("{%") {
+        let start = offset + pos;
+        let tag = &text[start + 2..];
+        if let Some(after) = tag.trim_start().strip_prefix("end") {
+            if let Some(after) = after.trim_start().strip_prefix("%}") {
+                if depth == 0 {
+                    return Some((&text[..start], after));
+                }
+                depth -= 1;
+                offset = text.len() - after.len();
+                continue;
+            }
+        }
+        if call_name(tag).is_some() {
+            depth += 1;
+        }
+        offset = start + 2;
+    }
+    None
+}
+
+/// Parse `key=value, ...)` keyword arguments up to the closing parenthesis.
+fn parse_args(text: &str) -> Result<(Map<String, Value>, &str), String> {
+    let mut args = Map::new();
+    let mut rest = text.trim_start();
+
+    loop {
+        if let Some(after) = rest.strip_prefix(')') {
+            return Ok((args, after));
+        }
+        let end = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        let key = &rest[..end];
+        if key.is_empty() {
+            return Err("expected an argument name or )".to_string());
+        }
+        let Some(after) = rest[end..].trim_start().strip_prefix('=') else {
+            return Err(format!("expected = after argument {key}"));
+        };
+        let (value, after) = parse_value(after.trim_start())?;
+        args.insert(key.to_string(), value);
+
+        rest = after.trim_start();
+        if let Some(after) = rest.strip_prefix(',') {
+            rest = after.trim_start();
+        } else if !rest.starts_with(')') {
+            return Err(format!("expected , or ) after argument {key}"));
+        }
+    }
+}
+
+/// Parse a string, number, boolean or array literal.
+fn parse_value(text: &str) -> Result<(Value, &str), String> {
+    let Some(first) = text.chars().next() else {
+        return Err("expected a value".to_string());
+    };
+
+    match first {
+        '"' | '\'' | '`' => {
+            let mut value = String::new();
+            let mut chars = text[1..].char_indices();
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '\\' => {
+                        if let Some((_, escaped)) = chars.next() {
+                            value.push(match escaped {
+                                'n' => '\n',
+                                't' => '\t',
+                                other => other,
+                            });
+                        }
+                    }
+                    c if c == first => return Ok((Value::String(value), &text[1 + i + 1..])),
+                    c => value.push(c),
+                }
+            }
+            Err("unterminated string".to_string())
+        }
+        '[' => {
+            let mut items = Vec::new();
+            let mut rest = text[1..].trim_start();
+            loop {
+                if let Some(after) = rest.strip_prefix(']') {
+                    return Ok((Value::Array(items), after));
+                }
+                let (item, after) = parse_value(rest)?;
+                items.push(item);
+                rest = after.trim_start();
+                if let Some(after) = rest.strip_prefix(',') {
+                    rest = after.trim_start();
+                } else if !rest.starts_with(']') {
+                    return Err("expected , or ] in array".to_string());
+                }
+            }
+        }
+        _ => {
+            let end = text
+                .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '+' | '.' | '_')))
+                .unwrap_or(text.len());
+            let (literal, rest) = text.split_at(end);
+            let value = match literal {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                _ => {
+                    if let Ok(n) = literal.parse::<i64>() {
+                        Value::from(n)
+                    } else if let Ok(n) = literal.parse::<f64>() {
+                        Value::from(n)
+                    } else {
+                        return Err(format!("invalid value '{literal}'"));
+                    }
+                }
+            };
+            Ok((value, rest))
+        }
+    }
+}
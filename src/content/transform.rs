@@ -0,0 +1,515 @@
+use std::collections::HashSet;
+use std::ops::Range;
+
+use pulldown_cmark::{CowStr, Event, LinkType, Tag, TagEnd};
+
+use crate::config::types::MarkdownConfig;
+use crate::content::links::WIKI_LINK_PREFIX;
+use crate::content::markdown::{html_escape, MarkdownError, SourceEvent};
+use crate::content::post::TocEntry;
+
+/// One step of rendering markdown. `MarkdownRenderer` parses a document
+/// into events, passes them through its transformers in order of
+/// priority, and writes the result as HTML. A transformer rewrites the
+/// events it is interested in; elements it renders itself are replaced by
+/// `Event::Html` holding their markup.
+///
+/// The built-in transformers, with their priorities, are:
+///
+/// | Priority | Name | |
+/// |---|---|---|
+/// | 5 | `autolinks` | Links bare URLs, with `gfm_autolinks` |
+/// | 10 | `wiki_links` | Marks `[[name]]` links for resolution |
+/// | 20 | `bundle_links` | Points links to page bundle assets at their URLs |
+/// | 30 | `code_processors` | Replaces blocks with `code_processors` output |
+/// | 40 | `code_blocks` | Highlights and annotates code blocks |
+/// | 50 | `math` | Renders TeX math as MathML |
+/// | 55 | `external_links` | Applies the external link policy |
+/// | 60 | `headings` | Gives headings anchors and collects them |
+/// | 90 | `admonitions` | Renders `> [!NOTE]` blockquotes |
+pub trait EventTransformer: Send + Sync {
+    /// Transformer name
+    fn name(&self) -> &str;
+
+    /// Priority (lower = runs first)
+    fn priority(&self) -> i32 {
+        100
+    }
+
+    /// Rewrite the events of a document
+    fn transform<'e>(
+        &self,
+        events: Vec<SourceEvent<'e>>,
+        context: &mut RenderContext,
+    ) -> Result<Vec<SourceEvent<'e>>, MarkdownError>;
+}
+
+impl<T: EventTransformer + ?Sized> EventTransformer for &T {
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    fn priority(&self) -> i32 {
+        (**self).priority()
+    }
+
+    fn transform<'e>(
+        &self,
+        events: Vec<SourceEvent<'e>>,
+        context: &mut RenderContext,
+    ) -> Result<Vec<SourceEvent<'e>>, MarkdownError> {
+        (**self).transform(events, context)
+    }
+}
+
+/// The document being rendered, shared by the transformers
+pub struct RenderContext<'c> {
+    /// Markdown source of the document
+    pub markdown: &'c str,
+    /// Markdown extensions enabled for the document
+    pub dialect: &'c MarkdownConfig,
+    /// URL and asset paths of the page bundle the document belongs to
+    pub bundle: Option<(&'c str, &'c [String])>,
+    /// Headings of the document, in order
+    pub headings: Vec<TocEntry>,
+    /// URLs of links to other sites, in order of first appearance
+    pub external_links: Vec<String>,
+}
+
+impl RenderContext<'_> {
+    /// An error at the start of an event's source range
+    pub fn error(&self, range: &Range<usize>, message: impl Into<String>) -> MarkdownError {
+        MarkdownError {
+            line: self.markdown[..range.start.min(self.markdown.len())]
+                .matches('\n')
+                .count()
+                + 1,
+            message: message.into(),
+        }
+    }
+}
+
+/// Write events as HTML
+pub fn to_html<'e>(events: impl IntoIterator<Item = SourceEvent<'e>>) -> String {
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, events.into_iter().map(|(event, _)| event));
+    html
+}
+
+/// An `Event::Html` with the source range of what it replaces
+pub fn html_event<'e>(html: String, range: Range<usize>) -> SourceEvent<'e> {
+    (Event::Html(CowStr::from(html)), range)
+}
+
+/// Replace every element whose start event matches `is_start` by the
+/// result of `replace`, which gets the element's events from its start to
+/// its end event. Elements nested in a matching element are left to
+/// `replace`.
+pub fn replace_elements<'e>(
+    events: Vec<SourceEvent<'e>>,
+    is_start: impl Fn(&Event) -> bool,
+    mut replace: impl FnMut(Vec<SourceEvent<'e>>) -> Result<Vec<SourceEvent<'e>>, MarkdownError>,
+) -> Result<Vec<SourceEvent<'e>>, MarkdownError> {
+    let mut output = Vec::with_capacity(events.len());
+    let mut element = Vec::new();
+    let mut depth = 0usize;
+
+    for (event, range) in events {
+        if depth == 0 && !is_start(&event) {
+            output.push((event, range));
+            continue;
+        }
+        match event {
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth -= 1,
+            _ => {}
+        }
+        element.push((event, range));
+        if depth == 0 {
+            output.extend(replace(std::mem::take(&mut element))?);
+        }
+    }
+
+    Ok(output)
+}
+
+/// Turns bare `https://`, `http://` and `www.` URLs in text into links, as
+/// GitHub does, when `gfm_autolinks` is enabled
+pub struct Autolinks;
+
+impl EventTransformer for Autolinks {
+    fn name(&self) -> &str {
+        "autolinks"
+    }
+
+    fn priority(&self) -> i32 {
+        5
+    }
+
+    fn transform<'e>(
+        &self,
+        events: Vec<SourceEvent<'e>>,
+        context: &mut RenderContext,
+    ) -> Result<Vec<SourceEvent<'e>>, MarkdownError> {
+        if !context.dialect.gfm_autolinks {
+            return Ok(events);
+        }
+        let mut output = Vec::with_capacity(events.len());
+        // Text of links, images and code blocks is left alone
+        let mut verbatim = 0usize;
+        // The parser may split a run of text into several events
+        let mut run = Vec::new();
+
+        for (event, range) in events {
+            if verbatim == 0 && matches!(event, Event::Text(_)) {
+                run.push((event, range));
+                continue;
+            }
+            link_urls(std::mem::take(&mut run), &mut output);
+            match event {
+                Event::Start(Tag::Link { .. } | Tag::Image { .. } | Tag::CodeBlock(_)) => {
+                    verbatim += 1
+                }
+                Event::End(TagEnd::Link | TagEnd::Image | TagEnd::CodeBlock) => verbatim -= 1,
+                _ => {}
+            }
+            output.push((event, range));
+        }
+        link_urls(run, &mut output);
+
+        Ok(output)
+    }
+}
+
+/// Add a run of text events to `output`, with links for the URLs in it
+fn link_urls<'e>(run: Vec<SourceEvent<'e>>, output: &mut Vec<SourceEvent<'e>>) {
+    let text: String = run
+        .iter()
+        .filter_map(|(event, _)| match event {
+            Event::Text(text) => Some(text.as_ref()),
+            _ => None,
+        })
+        .collect();
+    let urls = find_urls(&text);
+    if urls.is_empty() {
+        output.extend(run);
+        return;
+    }
+
+    let range = run[0].1.start..run[run.len() - 1].1.end;
+    let mut pos = 0;
+    for url in urls {
+        if url.start > pos {
+            let before = text[pos..url.start].to_string();
+            output.push((Event::Text(before.into()), range.clone()));
+        }
+        let shown = &text[url.clone()];
+        let dest_url = if shown.starts_with("www.") {
+            format!("http://{shown}")
+        } else {
+            shown.to_string()
+        };
+        let link = Tag::Link {
+            link_type: LinkType::Autolink,
+            dest_url: dest_url.into(),
+            title: CowStr::from(""),
+            id: CowStr::from(""),
+        };
+        output.push((Event::Start(link), range.clone()));
+        output.push((Event::Text(shown.to_string().into()), range.clone()));
+        output.push((Event::End(TagEnd::Link), range.clone()));
+        pos = url.end;
+    }
+    if pos < text.len() {
+        output.push((Event::Text(text[pos..].to_string().into()), range));
+    }
+}
+
+/// Byte ranges of the URLs in text, following GitHub's extended autolink
+/// rules: a URL starts a word and ends before whitespace or `<`, without
+/// trailing punctuation or unbalanced closing parentheses.
+fn find_urls(text: &str) -> Vec<Range<usize>> {
+    let mut urls = Vec::new();
+    let mut from = 0;
+
+    for (start, _) in text.char_indices() {
+        if start < from {
+            continue;
+        }
+        let rest = &text[start..];
+        let Some(prefix) = ["https://", "http://", "www."]
+            .into_iter()
+            .find(|prefix| rest.starts_with(prefix))
+        else {
+            continue;
+        };
+        let starts_word = text[..start]
+            .chars()
+            .next_back()
+            .is_none_or(|c| c.is_whitespace() || "*_~(".contains(c));
+        if !starts_word {
+            continue;
+        }
+
+        let mut url = &rest[..rest
+            .find(|c: char| c.is_whitespace() || c == '<')
+            .unwrap_or(rest.len())];
+        loop {
+            if let Some(trimmed) = url.strip_suffix(|c| "?!.,:*_~'\"".contains(c)) {
+                url = trimmed;
+            } else if url.ends_with(')') && url.matches(')').count() > url.matches('(').count() {
+                url = &url[..url.len() - 1];
+            } else {
+                break;
+            }
+        }
+
+        let host = match prefix {
+            "www." => url,
+            scheme => &url[scheme.len()..],
+        };
+        let domain = host.split(['/', '?', '#']).next().unwrap_or_default();
+        let valid_domain = domain.contains('.')
+            && domain.split('.').all(|label| !label.is_empty())
+            && domain
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '-' || c == '_' || c == '.' || c == ':');
+        if valid_domain {
+            urls.push(start..start + url.len());
+            from = start + url.len();
+        }
+    }
+    urls
+}
+
+/// Wiki links (`[[name]]`) are resolved by name once all content is loaded,
+/// together with `@/path.md` links. Until then their target is marked.
+pub struct WikiLinks;
+
+impl EventTransformer for WikiLinks {
+    fn name(&self) -> &str {
+        "wiki_links"
+    }
+
+    fn priority(&self) -> i32 {
+        10
+    }
+
+    fn transform<'e>(
+        &self,
+        events: Vec<SourceEvent<'e>>,
+        _context: &mut RenderContext,
+    ) -> Result<Vec<SourceEvent<'e>>, MarkdownError> {
+        Ok(events
+            .into_iter()
+            .map(|(event, range)| match event {
+                Event::Start(Tag::Link {
+                    link_type: link_type @ LinkType::WikiLink { .. },
+                    dest_url,
+                    title,
+                    id,
+                }) => {
+                    let link = Tag::Link {
+                        link_type,
+                        dest_url: format!("{WIKI_LINK_PREFIX}{dest_url}").into(),
+                        title,
+                        id,
+                    };
+                    (Event::Start(link), range)
+                }
+                other => (other, range),
+            })
+            .collect())
+    }
+}
+
+/// In page bundles, relative links and images that point at the bundle's
+/// assets get absolute URLs, so they also resolve in listings and feeds.
+pub struct BundleLinks;
+
+impl EventTransformer for BundleLinks {
+    fn name(&self) -> &str {
+        "bundle_links"
+    }
+
+    fn priority(&self) -> i32 {
+        20
+    }
+
+    fn transform<'e>(
+        &self,
+        events: Vec<SourceEvent<'e>>,
+        context: &mut RenderContext,
+    ) -> Result<Vec<SourceEvent<'e>>, MarkdownError> {
+        let Some((bundle_url, assets)) = context.bundle else {
+            return Ok(events);
+        };
+        let resolve = |dest: CowStr<'e>| -> CowStr<'e> {
+            let (path, fragment) = match dest.find(['?', '#']) {
+                Some(pos) => dest.split_at(pos),
+                None => (dest.as_ref(), ""),
+            };
+            let path = path.trim_start_matches("./");
+            if !assets.iter().any(|asset| asset == path) {
+                return dest;
+            }
+            format!("{}/{path}{fragment}", bundle_url.trim_end_matches('/')).into()
+        };
+
+        Ok(events
+            .into_iter()
+            .map(|(event, range)| match event {
+                Event::Start(Tag::Link {
+                    link_type,
+                    dest_url,
+                    title,
+                    id,
+                }) => {
+                    let link = Tag::Link {
+                        link_type,
+                        dest_url: resolve(dest_url),
+                        title,
+                        id,
+                    };
+                    (Event::Start(link), range)
+                }
+                Event::Start(Tag::Image {
+                    link_type,
+                    dest_url,
+                    title,
+                    id,
+                }) => {
+                    let image = Tag::Image {
+                        link_type,
+                        dest_url: resolve(dest_url),
+                        title,
+                        id,
+                    };
+                    (Event::Start(image), range)
+                }
+                other => (other, range),
+            })
+            .collect())
+    }
+}
+
+/// Gives headings unique ids and anchor links, keeping their inline
+/// markup, and collects them for the table of contents
+pub struct Headings;
+
+impl EventTransformer for Headings {
+    fn name(&self) -> &str {
+        "headings"
+    }
+
+    fn priority(&self) -> i32 {
+        60
+    }
+
+    fn transform<'e>(
+        &self,
+        events: Vec<SourceEvent<'e>>,
+        context: &mut RenderContext,
+    ) -> Result<Vec<SourceEvent<'e>>, MarkdownError> {
+        let mut used_ids = HashSet::new();
+
+        replace_elements(
+            events,
+            |event| matches!(event, Event::Start(Tag::Heading { .. })),
+            |mut heading| {
+                let (start, range) = heading.remove(0);
+                heading.pop();
+                let Event::Start(Tag::Heading {
+                    level,
+                    id,
+                    classes,
+                    attrs,
+                }) = start
+                else {
+                    unreachable!("replace_elements passes headings");
+                };
+                let level = level as u32;
+
+                let text = plain_text(&heading);
+                let id = match id {
+                    Some(id) => id.to_string(),
+                    None => match slug::slugify(&text) {
+                        slug if slug.is_empty() => {
+                            format!("heading-{}", context.headings.len() + 1)
+                        }
+                        slug => slug,
+                    },
+                };
+                let id = unique_id(id, &mut used_ids);
+
+                let mut attributes = format!(" id=\"{}\"", html_escape(&id));
+                if !classes.is_empty() {
+                    let classes: Vec<&str> = classes.iter().map(|class| class.as_ref()).collect();
+                    attributes.push_str(&format!(" class=\"{}\"", html_escape(&classes.join(" "))));
+                }
+                for (name, value) in &attrs {
+                    attributes.push_str(&format!(" {}", html_escape(name)));
+                    if let Some(value) = value {
+                        attributes.push_str(&format!("=\"{}\"", html_escape(value)));
+                    }
+                }
+
+                // Links cannot nest, so the table of contents gets their text
+                let title = to_html(
+                    heading
+                        .iter()
+                        .filter(|(event, _)| !is_link_markup(event))
+                        .cloned(),
+                );
+                let content = to_html(heading);
+
+                let html = format!(
+                    "<h{level}{attributes}><a href=\"#{id}\" class=\"anchor\">#</a> {content}</h{level}>\n",
+                    id = html_escape(&id),
+                );
+                context.headings.push(TocEntry {
+                    level,
+                    id,
+                    title,
+                    children: Vec::new(),
+                });
+                Ok(vec![html_event(html, range)])
+            },
+        )
+    }
+}
+
+/// Events that make up a link rather than its text, including links that
+/// an earlier transformer wrote as HTML
+fn is_link_markup(event: &Event) -> bool {
+    match event {
+        Event::Start(Tag::Link { .. }) | Event::End(TagEnd::Link) => true,
+        Event::FootnoteReference(_) => true,
+        Event::InlineHtml(html) => html.starts_with("<a ") || html.as_ref() == "</a>",
+        _ => false,
+    }
+}
+
+/// Text of inline events, for ids
+fn plain_text(events: &[SourceEvent]) -> String {
+    let mut text = String::new();
+    for (event, _) in events {
+        match event {
+            Event::Text(t) | Event::Code(t) | Event::InlineMath(t) => text.push_str(t),
+            Event::SoftBreak | Event::HardBreak => text.push(' '),
+            _ => {}
+        }
+    }
+    text
+}
+
+/// Suffix repeated heading ids with `-1`, `-2`, ... so anchors stay unique
+fn unique_id(id: String, used: &mut HashSet<String>) -> String {
+    let mut unique = id.clone();
+    let mut n = 0;
+    while !used.insert(unique.clone()) {
+        n += 1;
+        unique = format!("{id}-{n}");
+    }
+    unique
+}
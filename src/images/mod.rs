@@ -0,0 +1,378 @@
+pub mod responsive;
+
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::imageops::FilterType;
+use image::DynamicImage;
+use serde::Serialize;
+
+use crate::config::SiteConfig;
+use crate::error::ForgeResult;
+
+/// Output directory of processed images, and their directory in the cache
+pub const PROCESSED_IMAGES_DIR: &str = "processed_images";
+
+/// How to fit an image into the requested size
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResizeOp {
+    /// Exactly the given width and height, ignoring the aspect ratio
+    Scale,
+    /// The given width, keeping the aspect ratio
+    FitWidth,
+    /// The given height, keeping the aspect ratio
+    FitHeight,
+    /// Within the given width and height, keeping the aspect ratio
+    Fit,
+    /// Cover the given width and height, cropping the overflow from the center
+    Fill,
+}
+
+impl ResizeOp {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "scale" => Some(Self::Scale),
+            "fit_width" => Some(Self::FitWidth),
+            "fit_height" => Some(Self::FitHeight),
+            "fit" => Some(Self::Fit),
+            "fill" => Some(Self::Fill),
+            _ => None,
+        }
+    }
+}
+
+/// Encoding of a processed image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// PNG for PNG sources, JPEG otherwise
+    Auto,
+    Jpeg,
+    Png,
+    /// Lossless WebP
+    Webp,
+}
+
+impl OutputFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "auto" => Some(Self::Auto),
+            "jpg" | "jpeg" => Some(Self::Jpeg),
+            "png" => Some(Self::Png),
+            "webp" => Some(Self::Webp),
+            _ => None,
+        }
+    }
+
+    fn resolve(self, source: &Path) -> Self {
+        if self != Self::Auto {
+            return self;
+        }
+        match extension(source).as_str() {
+            "png" => Self::Png,
+            "webp" => Self::Webp,
+            _ => Self::Jpeg,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Webp => "webp",
+            Self::Jpeg | Self::Auto => "jpg",
+        }
+    }
+}
+
+/// A resize of one source image
+#[derive(Debug, Clone, Copy)]
+pub struct ResizeRequest {
+    pub op: ResizeOp,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub format: OutputFormat,
+    pub quality: u8,
+}
+
+/// A processed image, as returned to templates
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessedImage {
+    pub url: String,
+    pub width: u32,
+    pub height: u32,
+    pub orig_width: u32,
+    pub orig_height: u32,
+}
+
+/// Content hash and dimensions of a source image
+#[derive(Debug, Clone)]
+struct SourceInfo {
+    hash: String,
+    width: u32,
+    height: u32,
+}
+
+/// Resizes and re-encodes images on demand. Results are kept in
+/// `.forge_cache/processed_images/`, named after the source hash and the
+/// operation, so each variant is only encoded once.
+pub struct ImageProcessor {
+    base_url: String,
+    /// Directories that image paths are looked up in, in order
+    search_dirs: Vec<PathBuf>,
+    cache_dir: PathBuf,
+    default_quality: u8,
+    sources: Mutex<HashMap<PathBuf, SourceInfo>>,
+    /// The last decoded source, as its variants are usually made together
+    decoded: Mutex<Option<(PathBuf, Arc<DynamicImage>)>>,
+    /// Processed files used by this build
+    used: Mutex<BTreeSet<String>>,
+}
+
+impl ImageProcessor {
+    pub fn new(site_dir: &Path, config: &SiteConfig) -> Self {
+        Self {
+            base_url: config.base_url.trim_end_matches('/').to_string(),
+            search_dirs: vec![
+                site_dir.join("static"),
+                site_dir.join("themes").join(&config.theme).join("static"),
+                site_dir.join("content"),
+            ],
+            cache_dir: site_dir.join(".forge_cache").join(PROCESSED_IMAGES_DIR),
+            default_quality: config.images.quality,
+            sources: Mutex::new(HashMap::new()),
+            decoded: Mutex::new(None),
+            used: Mutex::new(BTreeSet::new()),
+        }
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub fn default_quality(&self) -> u8 {
+        self.default_quality
+    }
+
+    /// Find an image by its path relative to `static/` (the site's or the
+    /// theme's) or `content/`
+    pub fn find(&self, path: &str) -> Option<PathBuf> {
+        let path = path.trim_start_matches('/');
+        self.search_dirs
+            .iter()
+            .map(|dir| dir.join(path))
+            .find(|candidate| candidate.is_file())
+    }
+
+    /// Width and height of a source image
+    pub fn dimensions(&self, source: &Path) -> Result<(u32, u32), String> {
+        let info = self.source_info(source)?;
+        Ok((info.width, info.height))
+    }
+
+    fn source_info(&self, source: &Path) -> Result<SourceInfo, String> {
+        if let Some(info) = self.sources.lock().unwrap().get(source) {
+            return Ok(info.clone());
+        }
+        let data = fs::read(source).map_err(|e| e.to_string())?;
+        let (width, height) = image::ImageReader::new(std::io::Cursor::new(&data))
+            .with_guessed_format()
+            .map_err(|e| e.to_string())?
+            .into_dimensions()
+            .map_err(|e| e.to_string())?;
+        let info = SourceInfo {
+            hash: blake3::hash(&data).to_hex().to_string(),
+            width,
+            height,
+        };
+        self.sources
+            .lock()
+            .unwrap()
+            .insert(source.to_path_buf(), info.clone());
+        Ok(info)
+    }
+
+    /// Resize an image, reusing the cached result of an earlier build
+    pub fn resize(&self, source: &Path, request: &ResizeRequest) -> Result<ProcessedImage, String> {
+        let info = self.source_info(source)?;
+        let (width, height) = target_size(request, info.width, info.height)?;
+        let format = request.format.resolve(source);
+
+        let operation = format!(
+            "{:?} {width}x{height} {:?} q{}",
+            request.op, format, request.quality
+        );
+        let key = blake3::hash(format!("{}\n{operation}", info.hash).as_bytes()).to_hex();
+        let stem = source
+            .file_stem()
+            .map(|stem| slug::slugify(stem.to_string_lossy()))
+            .unwrap_or_default();
+        let file_name = format!(
+            "{stem}-{width}x{height}-{}.{}",
+            &key[..12],
+            format.extension()
+        );
+
+        let cached = self.cache_dir.join(&file_name);
+        if !cached.exists() {
+            let image = self.decode(source)?;
+            let resized = match request.op {
+                ResizeOp::Fill => image.resize_to_fill(width, height, FilterType::Lanczos3),
+                _ => image.resize_exact(width, height, FilterType::Lanczos3),
+            };
+            fs::create_dir_all(&self.cache_dir).map_err(|e| e.to_string())?;
+            encode(&resized, &cached, format, request.quality)?;
+        }
+        self.used.lock().unwrap().insert(file_name.clone());
+
+        Ok(ProcessedImage {
+            url: format!("{}/{PROCESSED_IMAGES_DIR}/{file_name}", self.base_url),
+            width,
+            height,
+            orig_width: info.width,
+            orig_height: info.height,
+        })
+    }
+
+    fn decode(&self, source: &Path) -> Result<Arc<DynamicImage>, String> {
+        let mut decoded = self.decoded.lock().unwrap();
+        if let Some((path, image)) = decoded.as_ref() {
+            if path == source {
+                return Ok(Arc::clone(image));
+            }
+        }
+        let image = Arc::new(image::open(source).map_err(|e| e.to_string())?);
+        *decoded = Some((source.to_path_buf(), Arc::clone(&image)));
+        Ok(image)
+    }
+
+    /// Copy the images processed in this build to the output directory.
+    /// With `prune`, processed images that were not used are removed from
+    /// the output and the cache; only do this when every page was rendered.
+    pub fn write_outputs(&self, output_dir: &Path, prune: bool) -> ForgeResult<usize> {
+        let used = self.used.lock().unwrap();
+        let output_images = output_dir.join(PROCESSED_IMAGES_DIR);
+        let mut written = 0;
+
+        for file_name in used.iter() {
+            let target = output_images.join(file_name);
+            // Names contain a hash of the content, so existing files are current
+            if !target.exists() {
+                fs::create_dir_all(&output_images)?;
+                fs::copy(self.cache_dir.join(file_name), &target)?;
+                written += 1;
+            }
+        }
+
+        if prune {
+            for dir in [&output_images, &self.cache_dir] {
+                let Ok(entries) = fs::read_dir(dir) else {
+                    continue;
+                };
+                for entry in entries.filter_map(|e| e.ok()) {
+                    let name = entry.file_name().to_string_lossy().to_string();
+                    if !used.contains(&name) {
+                        fs::remove_file(entry.path())?;
+                    }
+                }
+            }
+        }
+
+        Ok(written)
+    }
+}
+
+/// Size of the resized image. Fitting operations never enlarge an image.
+fn target_size(request: &ResizeRequest, width: u32, height: u32) -> Result<(u32, u32), String> {
+    let scaled = |numerator: u32, denominator: u32, value: u32| -> u32 {
+        ((value as f64 * numerator as f64 / denominator as f64).round() as u32).max(1)
+    };
+    let (w, h) = (request.width, request.height);
+
+    match request.op {
+        ResizeOp::Scale | ResizeOp::Fill => match (w, h) {
+            (Some(w), Some(h)) => Ok((w, h)),
+            _ => Err(format!("{:?} needs both a width and a height", request.op)),
+        },
+        ResizeOp::FitWidth => {
+            let w = w.ok_or("fit_width needs a width")?.min(width);
+            Ok((w, scaled(w, width, height)))
+        }
+        ResizeOp::FitHeight => {
+            let h = h.ok_or("fit_height needs a height")?.min(height);
+            Ok((scaled(h, height, width), h))
+        }
+        ResizeOp::Fit => {
+            let (w, h) = match (w, h) {
+                (Some(w), Some(h)) => (w, h),
+                _ => return Err("fit needs both a width and a height".to_string()),
+            };
+            if width <= w && height <= h {
+                return Ok((width, height));
+            }
+            // Scale by the more constraining side
+            if w as u64 * height as u64 <= h as u64 * width as u64 {
+                Ok((w, scaled(w, width, height)))
+            } else {
+                Ok((scaled(h, height, width), h))
+            }
+        }
+    }
+}
+
+/// Encode an image to `path`. The file is written under a temporary name and
+/// renamed into place, so that pages rendered in parallel never see it half
+/// written.
+fn encode(
+    image: &DynamicImage,
+    path: &Path,
+    format: OutputFormat,
+    quality: u8,
+) -> Result<(), String> {
+    static NEXT_TEMP: AtomicUsize = AtomicUsize::new(0);
+    let temp = path.with_extension(format!(
+        "{}.{}.tmp",
+        std::process::id(),
+        NEXT_TEMP.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let file = fs::File::create(&temp).map_err(|e| e.to_string())?;
+    let mut writer = BufWriter::new(file);
+    let result = match format {
+        OutputFormat::Png => image.write_with_encoder(PngEncoder::new(&mut writer)),
+        OutputFormat::Webp => {
+            // The pure-Rust WebP encoder takes 8-bit RGB(A) only
+            let image = if image.color().has_alpha() {
+                DynamicImage::ImageRgba8(image.to_rgba8())
+            } else {
+                DynamicImage::ImageRgb8(image.to_rgb8())
+            };
+            image.write_with_encoder(WebPEncoder::new_lossless(&mut writer))
+        }
+        OutputFormat::Jpeg | OutputFormat::Auto => {
+            let image = DynamicImage::ImageRgb8(image.to_rgb8());
+            image.write_with_encoder(JpegEncoder::new_with_quality(&mut writer, quality))
+        }
+    };
+    let result = result
+        .map_err(|e| e.to_string())
+        .and_then(|()| writer.flush().map_err(|e| e.to_string()))
+        .and_then(|()| fs::rename(&temp, path).map_err(|e| e.to_string()));
+    if result.is_err() {
+        // Do not leave a broken file in the cache
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+/// Lowercased file extension
+pub fn extension(path: &Path) -> String {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
@@ -0,0 +1,166 @@
+use std::path::{Path, PathBuf};
+
+use crate::config::types::ImageConfig;
+use crate::images::{extension, ImageProcessor, OutputFormat, ResizeOp, ResizeRequest};
+
+/// Formats that get resized variants; GIFs may be animated and SVGs scale
+const RESPONSIVE_EXTENSIONS: [&str; 4] = ["jpg", "jpeg", "png", "webp"];
+
+/// Gives the local images in rendered HTML intrinsic dimensions, lazy
+/// loading and a `srcset` of resized variants, plus WebP variants of PNGs in
+/// a `<picture>` when enabled. `bundle` is the URL and directory of a page
+/// bundle whose assets the images may be.
+pub fn responsive_images(
+    html: &str,
+    processor: &ImageProcessor,
+    config: &ImageConfig,
+    bundle: Option<(&str, &Path)>,
+) -> Result<String, String> {
+    if !html.contains("<img ") {
+        return Ok(html.to_string());
+    }
+
+    let mut output = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find("<img ") {
+        output.push_str(&rest[..start]);
+        let Some(len) = rest[start..].find('>') else {
+            break;
+        };
+        let tag = &rest[start..start + len + 1];
+        match rewrite_img(tag, processor, config, bundle)? {
+            Some(replacement) => output.push_str(&replacement),
+            None => output.push_str(tag),
+        }
+        rest = &rest[start + len + 1..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+fn rewrite_img(
+    tag: &str,
+    processor: &ImageProcessor,
+    config: &ImageConfig,
+    bundle: Option<(&str, &Path)>,
+) -> Result<Option<String>, String> {
+    // Leave images that are already responsive alone
+    if attribute(tag, "srcset").is_some() {
+        return Ok(None);
+    }
+    let Some(src) = attribute(tag, "src") else {
+        return Ok(None);
+    };
+    let Some(source) = find_source(src, processor, bundle) else {
+        return Ok(None);
+    };
+    let ext = extension(&source);
+    if !RESPONSIVE_EXTENSIONS.contains(&ext.as_str()) {
+        return Ok(None);
+    }
+
+    let report = |e: String| format!("image {src}: {e}");
+    // Files that only look like images are left as they are
+    let (width, height) = match processor.dimensions(&source) {
+        Ok(dimensions) => dimensions,
+        Err(e) => {
+            tracing::warn!("Not processing image {}: {e}", source.display());
+            return Ok(None);
+        }
+    };
+
+    let variants = |format: OutputFormat| -> Result<Vec<String>, String> {
+        let mut srcset = Vec::new();
+        for &w in config.widths.iter().filter(|&&w| w < width) {
+            let image = processor
+                .resize(
+                    &source,
+                    &ResizeRequest {
+                        op: ResizeOp::FitWidth,
+                        width: Some(w),
+                        height: None,
+                        format,
+                        quality: config.quality,
+                    },
+                )
+                .map_err(report)?;
+            srcset.push(format!("{} {}w", image.url, image.width));
+        }
+        Ok(srcset)
+    };
+
+    let mut attrs = String::new();
+    let mut srcset = variants(OutputFormat::Auto)?;
+    if !srcset.is_empty() {
+        srcset.push(format!("{src} {width}w"));
+        attrs.push_str(&format!(
+            " srcset=\"{}\" sizes=\"{}\"",
+            srcset.join(", "),
+            config.sizes
+        ));
+    }
+    attrs.push_str(&format!(" width=\"{width}\" height=\"{height}\""));
+    if config.lazy && attribute(tag, "loading").is_none() {
+        attrs.push_str(" loading=\"lazy\" decoding=\"async\"");
+    }
+
+    let body = tag.trim_end_matches('>').trim_end_matches('/').trim_end();
+    let img = format!("{body}{attrs} />");
+
+    // The WebP encoder is lossless, which only pays off against PNG; a
+    // lossless WebP of a photo is several times larger than its JPEG
+    if !config.webp || ext != "png" || srcset.is_empty() {
+        return Ok(Some(img));
+    }
+    let mut webp = variants(OutputFormat::Webp)?;
+    let full = processor
+        .resize(
+            &source,
+            &ResizeRequest {
+                op: ResizeOp::FitWidth,
+                width: Some(width),
+                height: None,
+                format: OutputFormat::Webp,
+                quality: config.quality,
+            },
+        )
+        .map_err(report)?;
+    webp.push(format!("{} {width}w", full.url));
+    Ok(Some(format!(
+        "<picture><source type=\"image/webp\" srcset=\"{}\" sizes=\"{}\">{img}</picture>",
+        webp.join(", "),
+        config.sizes
+    )))
+}
+
+/// The source file of an image URL: an asset of the bundle, or a file in
+/// `static/` or `content/`
+fn find_source(
+    src: &str,
+    processor: &ImageProcessor,
+    bundle: Option<(&str, &Path)>,
+) -> Option<PathBuf> {
+    if let Some((bundle_url, bundle_dir)) = bundle {
+        if let Some(asset) = src.strip_prefix(bundle_url.trim_end_matches('/')) {
+            let path = bundle_dir.join(asset.trim_start_matches('/'));
+            if path.is_file() {
+                return Some(path);
+            }
+        }
+    }
+
+    let path = match src.strip_prefix(processor.base_url()) {
+        Some(path) if path.starts_with('/') => path,
+        _ if src.starts_with('/') && !src.starts_with("//") => src,
+        _ => return None,
+    };
+    processor.find(path)
+}
+
+/// Value of an attribute of an HTML tag
+fn attribute<'t>(tag: &'t str, name: &str) -> Option<&'t str> {
+    let needle = format!(" {name}=\"");
+    let start = tag.find(&needle)? + needle.len();
+    let len = tag[start..].find('"')?;
+    Some(&tag[start..start + len])
+}
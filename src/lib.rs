@@ -3,6 +3,7 @@ pub mod config;
 pub mod content;
 pub mod error;
 pub mod i18n;
+pub mod images;
 pub mod output;
 pub mod pipeline;
 pub mod plugin;
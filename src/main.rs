@@ -2,7 +2,7 @@ use clap::Parser;
 use tracing_subscriber::EnvFilter;
 
 use forge::cli::commands::{Cli, Commands};
-use forge::cli::{build, clean, new, serve};
+use forge::cli::{build, clean, new, serve, syntax};
 
 fn main() {
     tracing_subscriber::fmt()
@@ -20,17 +20,24 @@ fn main() {
         Commands::Build {
             root,
             drafts,
+            future,
             force,
-        } => build::build_site(&root, drafts, force),
+        } => build::build_site(&root, drafts, future, force),
         Commands::Serve {
             root,
             port,
             drafts,
+            future,
             open,
         } => {
             let rt = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
-            rt.block_on(serve::serve_site(&root, port, drafts, open))
+            rt.block_on(serve::serve_site(&root, port, drafts, future, open))
         }
+        Commands::SyntaxCss {
+            theme,
+            root,
+            output,
+        } => syntax::syntax_css(&root, &theme, output.as_deref()),
         Commands::Clean { root } => clean::clean_site(&root),
     };
 
@@ -21,6 +21,18 @@ pub fn copy_static_assets(site_dir: &Path, theme: &str, output_dir: &Path) -> Fo
     Ok(())
 }
 
+/// Copy the assets of a page bundle next to its rendered index.html.
+pub fn copy_bundle_assets(bundle_dir: &Path, assets: &[String], dest: &Path) -> ForgeResult<()> {
+    for asset in assets {
+        let target = dest.join(asset);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(bundle_dir.join(asset), &target)?;
+    }
+    Ok(())
+}
+
 fn copy_dir_recursive(src: &Path, dest: &Path) -> ForgeResult<()> {
     for entry in WalkDir::new(src).into_iter().filter_map(|e| e.ok()) {
         let path = entry.path();
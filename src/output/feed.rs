@@ -29,7 +29,9 @@ pub fn generate_rss(posts: &[Post], config: &SiteConfig) -> ForgeResult<String>
         .link(&config.base_url)
         .description(&config.description)
         .language(Some(config.language.clone()))
-        .last_build_date(Some(Utc::now().to_rfc2822()))
+        .last_build_date(Some(
+            Utc::now().with_timezone(&config.timezone).to_rfc2822(),
+        ))
         .items(items)
         .build();
 
@@ -50,7 +52,7 @@ pub fn generate_atom(posts: &[Post], config: &SiteConfig) -> ForgeResult<String>
     xml.push_str(&format!("  <id>{base_url}/</id>\n"));
     xml.push_str(&format!(
         "  <updated>{}</updated>\n",
-        Utc::now().to_rfc3339()
+        Utc::now().with_timezone(&config.timezone).to_rfc3339()
     ));
 
     if !config.author.is_empty() {
@@ -68,9 +70,13 @@ pub fn generate_atom(posts: &[Post], config: &SiteConfig) -> ForgeResult<String>
         xml.push_str(&format!("    <link href=\"{}\" />\n", post.permalink));
         xml.push_str(&format!("    <id>{}</id>\n", post.permalink));
         xml.push_str(&format!(
-            "    <updated>{}</updated>\n",
+            "    <published>{}</published>\n",
             post.date.to_rfc3339()
         ));
+        xml.push_str(&format!(
+            "    <updated>{}</updated>\n",
+            post.last_modified().to_rfc3339()
+        ));
         if !post.description.is_empty() {
             xml.push_str(&format!(
                 "    <summary>{}</summary>\n",
@@ -1,5 +1,6 @@
 pub mod assets;
 pub mod feed;
+pub mod redirects;
 pub mod search_index;
 pub mod sitemap;
 pub mod writer;
@@ -0,0 +1,176 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::content::permalink;
+use crate::error::{ForgeError, ForgeResult};
+
+const REDIRECTS_FILE: &str = "redirects.toml";
+
+/// A redirect from an old URL path of the site to a page's current permalink
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Redirect {
+    /// URL path being redirected, e.g. "/2019/04/old-slug/"
+    pub from: String,
+    /// Absolute URL of the target
+    pub to: String,
+}
+
+impl Redirect {
+    /// Build a redirect from a front matter alias. Aliases ending in `.html`
+    /// are kept as files; everything else is treated as a directory URL.
+    pub fn from_alias(alias: &str, to: &str) -> Self {
+        let from = if alias.ends_with(".html") {
+            format!("/{}", alias.trim_start_matches('/'))
+        } else {
+            permalink::normalize(alias)
+        };
+        Self {
+            from,
+            to: to.to_string(),
+        }
+    }
+
+    /// The file, relative to the output directory, holding the redirect stub.
+    pub fn output_file(&self) -> String {
+        if self.from.ends_with(".html") {
+            self.from.trim_start_matches('/').to_string()
+        } else {
+            crate::output::writer::page_file(&self.from)
+        }
+    }
+}
+
+/// A redirect recorded automatically after a source file's permalink changed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedRedirect {
+    /// Old URL path
+    pub from: String,
+    /// URL path the source moved to when the redirect was recorded
+    pub to: String,
+    /// Source file, relative to the site root. While it exists the redirect
+    /// follows its current permalink.
+    pub source: String,
+}
+
+/// The site's `redirects.toml`, meant to be committed alongside the content
+/// so that moved permalinks keep redirecting after the build cache is gone.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RedirectStore {
+    #[serde(default)]
+    pub redirects: Vec<RecordedRedirect>,
+    #[serde(skip)]
+    path: PathBuf,
+    #[serde(skip)]
+    changed: bool,
+}
+
+impl RedirectStore {
+    pub fn load(site_dir: &Path) -> ForgeResult<Self> {
+        let path = site_dir.join(REDIRECTS_FILE);
+        let mut store = if path.exists() {
+            let content = fs::read_to_string(&path)?;
+            toml::from_str(&content)
+                .map_err(|e| ForgeError::Config(format!("{}: {}", path.display(), e.message())))?
+        } else {
+            RedirectStore::default()
+        };
+        store.path = path;
+        Ok(store)
+    }
+
+    /// Record that `source` moved from one URL path to another, replacing
+    /// any earlier redirect from the same path.
+    pub fn record(&mut self, from: &str, to: &str, source: &str) {
+        self.remove(from);
+        self.redirects.push(RecordedRedirect {
+            from: from.to_string(),
+            to: to.to_string(),
+            source: source.to_string(),
+        });
+        self.changed = true;
+    }
+
+    pub fn remove(&mut self, from: &str) {
+        let before = self.redirects.len();
+        self.redirects.retain(|r| r.from != from);
+        self.changed |= self.redirects.len() != before;
+    }
+
+    /// Write the file back if redirects were recorded or dropped.
+    pub fn save(&mut self) -> ForgeResult<()> {
+        if !self.changed {
+            return Ok(());
+        }
+        self.redirects.sort_by(|a, b| a.from.cmp(&b.from));
+        let body = toml::to_string(&*self)
+            .map_err(|e| ForgeError::Build(format!("Failed to serialize redirects: {e}")))?;
+        fs::write(
+            &self.path,
+            format!("# Redirects recorded by Forge when a permalink changed.\n\n{body}"),
+        )?;
+        self.changed = false;
+        Ok(())
+    }
+}
+
+/// HTML stub that sends browsers and crawlers to the redirect target.
+pub fn redirect_page(redirect: &Redirect) -> String {
+    let to = attr_escape(&redirect.to);
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+    <meta charset="utf-8">
+    <title>Redirecting&hellip;</title>
+    <link rel="canonical" href="{to}">
+    <meta name="robots" content="noindex">
+    <meta http-equiv="refresh" content="0; url={to}">
+</head>
+<body>
+    <p>This page has moved to <a href="{to}">{to}</a>.</p>
+</body>
+</html>
+"#
+    )
+}
+
+/// Netlify `_redirects` file with permanent redirects.
+pub fn generate_netlify(redirects: &[Redirect], base_url: &str) -> String {
+    let mut out = String::from("# Generated by Forge\n");
+    for redirect in redirects {
+        out.push_str(&format!(
+            "{}  {}  301\n",
+            redirect.from,
+            permalink::url_path(base_url, &redirect.to)
+        ));
+    }
+    out
+}
+
+/// nginx `map` block from old to new URL paths. Include it in the `http`
+/// context and add `if ($forge_redirect) { return 301 $forge_redirect; }`
+/// to the server block.
+pub fn generate_nginx_map(redirects: &[Redirect], base_url: &str) -> String {
+    let mut out = String::from("# Generated by Forge\nmap $uri $forge_redirect {\n");
+    for redirect in redirects {
+        out.push_str(&format!(
+            "    {} {};\n",
+            nginx_quote(&redirect.from),
+            nginx_quote(permalink::url_path(base_url, &redirect.to))
+        ));
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn nginx_quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn attr_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -1,12 +1,14 @@
 use crate::config::SiteConfig;
 use crate::content::page::Page;
 use crate::content::post::Post;
+use crate::content::section::Section;
 use crate::taxonomy::TaxonomyCollection;
 use std::collections::HashMap;
 
 pub fn generate_sitemap(
     posts: &[Post],
     pages: &[Page],
+    sections: &[Section],
     taxonomies: &HashMap<String, TaxonomyCollection>,
     config: &SiteConfig,
 ) -> String {
@@ -26,18 +28,37 @@ pub fn generate_sitemap(
         xml.push_str(&format!(
             "  <url>\n    <loc>{}</loc>\n    <lastmod>{}</lastmod>\n    <priority>0.8</priority>\n  </url>\n",
             post.permalink,
-            post.date.format("%Y-%m-%d")
+            post.last_modified().format("%Y-%m-%d")
         ));
     }
 
     // Pages
     for page in pages {
+        let lastmod = page
+            .updated
+            .map(|date| format!("\n    <lastmod>{}</lastmod>", date.format("%Y-%m-%d")))
+            .unwrap_or_default();
         xml.push_str(&format!(
-            "  <url>\n    <loc>{}</loc>\n    <priority>0.6</priority>\n  </url>\n",
+            "  <url>\n    <loc>{}</loc>{lastmod}\n    <priority>0.6</priority>\n  </url>\n",
             page.permalink
         ));
     }
 
+    // Sections and their entries
+    for section in sections {
+        xml.push_str(&format!(
+            "  <url>\n    <loc>{}</loc>\n    <priority>0.6</priority>\n  </url>\n",
+            section.permalink
+        ));
+        for post in &section.posts {
+            xml.push_str(&format!(
+                "  <url>\n    <loc>{}</loc>\n    <lastmod>{}</lastmod>\n    <priority>0.7</priority>\n  </url>\n",
+                post.permalink,
+                post.last_modified().format("%Y-%m-%d")
+            ));
+        }
+    }
+
     // Taxonomy pages
     for collection in taxonomies.values() {
         xml.push_str(&format!(
@@ -15,13 +15,30 @@ pub fn write_html(output_dir: &Path, relative_path: &str, content: &str) -> Forg
 
 /// Write an index.html inside a directory path (for clean URLs).
 pub fn write_page(output_dir: &Path, url_path: &str, content: &str) -> ForgeResult<()> {
+    write_html(output_dir, &page_file(url_path), content)
+}
+
+/// The file, relative to the output directory, that `write_page` writes for a URL path.
+pub fn page_file(url_path: &str) -> String {
     let clean_path = url_path.trim_matches('/');
-    let dir_path = if clean_path.is_empty() {
-        output_dir.to_path_buf()
+    if clean_path.is_empty() {
+        "index.html".to_string()
     } else {
-        output_dir.join(clean_path)
-    };
-    fs::create_dir_all(&dir_path)?;
-    fs::write(dir_path.join("index.html"), content)?;
+        format!("{clean_path}/index.html")
+    }
+}
+
+/// Remove a previously generated file, along with any directories it leaves empty.
+pub fn remove_output(path: &Path) -> ForgeResult<()> {
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+    // Clean up directories left empty, e.g. `2024/05/` of a dated permalink.
+    // `remove_dir` only succeeds on empty directories.
+    for dir in path.ancestors().skip(1) {
+        if fs::remove_dir(dir).is_err() {
+            break;
+        }
+    }
     Ok(())
 }
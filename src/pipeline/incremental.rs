@@ -1,9 +1,12 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
 
+use serde::Serialize;
+
 use crate::error::ForgeResult;
-use crate::types::BuildManifest;
+use crate::render::dependencies::TemplateGraph;
+use crate::types::{BuildManifest, FileRecord};
 
 const CACHE_DIR: &str = ".forge_cache";
 const MANIFEST_FILE: &str = "manifest.json";
@@ -21,7 +24,10 @@ impl IncrementalCache {
 
         let manifest = if !force && manifest_path.exists() {
             let data = fs::read_to_string(&manifest_path)?;
-            serde_json::from_str(&data).unwrap_or_else(|_| BuildManifest::new())
+            serde_json::from_str::<BuildManifest>(&data)
+                .ok()
+                .filter(|m| m.version == env!("CARGO_PKG_VERSION"))
+                .unwrap_or_default()
         } else {
             BuildManifest::new()
         };
@@ -43,6 +49,17 @@ impl IncrementalCache {
         }
     }
 
+    /// Whether the non-content inputs of a source file changed since the last build
+    pub fn deps_changed(&self, path: &str, deps_hash: &str) -> bool {
+        if self.force {
+            return true;
+        }
+        match self.manifest.file_hashes.get(path) {
+            Some(record) => record.deps_hash != deps_hash,
+            None => true,
+        }
+    }
+
     pub fn config_changed(&self, config_hash: &str) -> bool {
         if self.force {
             return true;
@@ -50,30 +67,94 @@ impl IncrementalCache {
         self.manifest.config_hash != config_hash
     }
 
-    pub fn templates_changed(&self, template_hash: &str) -> bool {
+    /// Whether the templates used to render a source file changed, either
+    /// because a different template chain is used now or because one of the
+    /// templates in the chain was edited.
+    pub fn templates_changed(&self, path: &str, graph: &TemplateGraph) -> bool {
+        if self.force {
+            return true;
+        }
+        let Some(record) = self.manifest.file_hashes.get(path) else {
+            return true;
+        };
+        record.template_deps.is_empty()
+            || record.template_deps.iter().any(|name| {
+                self.manifest.template_hashes.get(name).map(String::as_str) != graph.hash(name)
+            })
+    }
+
+    pub fn site_changed(&self, site_hash: &str) -> bool {
+        if self.force {
+            return true;
+        }
+        self.manifest.site_hash != site_hash
+    }
+
+    /// Whether the inputs of a generated output changed since the last build
+    pub fn output_changed(&self, key: &str, hash: &str) -> bool {
         if self.force {
             return true;
         }
-        self.manifest.template_hash != template_hash
+        self.manifest.outputs.get(key).is_none_or(|h| h != hash)
+    }
+
+    pub fn file_record(&self, path: &str) -> Option<&FileRecord> {
+        self.manifest.file_hashes.get(path)
+    }
+
+    pub fn update_file(&mut self, path: String, record: FileRecord) {
+        self.manifest.file_hashes.insert(path, record);
+    }
+
+    pub fn update_output(&mut self, key: String, hash: String) {
+        self.manifest.outputs.insert(key, hash);
     }
 
-    pub fn update_file(&mut self, path: String, content_hash: String, output_path: PathBuf) {
-        self.manifest.file_hashes.insert(
-            path,
-            crate::types::FileRecord {
-                content_hash,
-                output_path,
-                template_deps: Vec::new(),
-            },
-        );
+    /// Drop records for source files that no longer exist, returning the
+    /// output paths they produced.
+    pub fn prune_files(&mut self, live: &HashSet<String>) -> Vec<PathBuf> {
+        let stale: Vec<String> = self
+            .manifest
+            .file_hashes
+            .keys()
+            .filter(|path| !live.contains(*path))
+            .cloned()
+            .collect();
+
+        stale
+            .into_iter()
+            .filter_map(|path| self.manifest.file_hashes.remove(&path))
+            .map(|record| record.output_path)
+            .collect()
+    }
+
+    /// Drop records for generated outputs that were not produced by this
+    /// build, returning their keys.
+    pub fn prune_outputs(&mut self, live: &HashSet<String>) -> Vec<String> {
+        let stale: Vec<String> = self
+            .manifest
+            .outputs
+            .keys()
+            .filter(|key| !live.contains(*key))
+            .cloned()
+            .collect();
+
+        for key in &stale {
+            self.manifest.outputs.remove(key);
+        }
+        stale
     }
 
     pub fn set_config_hash(&mut self, hash: String) {
         self.manifest.config_hash = hash;
     }
 
-    pub fn set_template_hash(&mut self, hash: String) {
-        self.manifest.template_hash = hash;
+    pub fn set_template_hashes(&mut self, hashes: HashMap<String, String>) {
+        self.manifest.template_hashes = hashes;
+    }
+
+    pub fn set_site_hash(&mut self, hash: String) {
+        self.manifest.site_hash = hash;
     }
 
     pub fn save(&mut self) -> ForgeResult<()> {
@@ -84,7 +165,16 @@ impl IncrementalCache {
         Ok(())
     }
 
-    pub fn file_hashes(&self) -> &HashMap<String, crate::types::FileRecord> {
+    pub fn file_hashes(&self) -> &HashMap<String, FileRecord> {
         &self.manifest.file_hashes
     }
 }
+
+/// Hash any serializable value with BLAKE3. The value goes through
+/// `serde_json::Value` first so that map keys are hashed in sorted order.
+pub fn hash_value<T: Serialize + ?Sized>(value: &T) -> String {
+    let data = serde_json::to_value(value)
+        .and_then(|v| serde_json::to_vec(&v))
+        .unwrap_or_default();
+    blake3::hash(&data).to_hex().to_string()
+}
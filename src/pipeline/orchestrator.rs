@@ -1,25 +1,47 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::Instant;
 
 use rayon::prelude::*;
 
+use crate::config::types::HighlightStyle;
 use crate::config::SiteConfig;
-use crate::content::loader::ContentLoader;
-use crate::error::ForgeResult;
+use crate::content::git::GitHistory;
+use crate::content::highlight::Highlighter;
+use crate::content::links;
+use crate::content::loader::{ContentLoader, WithheldItem};
+use crate::content::page::Page;
+use crate::content::permalink;
+use crate::content::post::Post;
+use crate::content::processors::CodeProcessors;
+use crate::content::section::{Section, SectionRef};
+use crate::error::{ForgeError, ForgeResult};
 use crate::i18n::translator::Translator;
+use crate::images::ImageProcessor;
+use crate::output::redirects::{self, Redirect, RedirectStore};
 use crate::output::{assets, feed, search_index, sitemap, writer};
-use crate::pipeline::incremental::IncrementalCache;
+use crate::pipeline::incremental::{hash_value, IncrementalCache};
+use crate::plugin::hooks::Plugin;
+use crate::plugin::registry::PluginRegistry;
 use crate::render::context;
+use crate::render::dependencies::TemplateGraph;
 use crate::render::engine;
 use crate::render::pagination::Paginator;
 use crate::taxonomy::builder::build_taxonomies;
-use crate::types::{PostRef, Site};
+use crate::taxonomy::TaxonomyRef;
+use crate::types::{FileRecord, PostRef, Site};
+
+const NGINX_REDIRECTS: &str = "nginx-redirects.conf";
 
 pub struct PipelineOrchestrator {
     site_dir: PathBuf,
     config: SiteConfig,
     force: bool,
+    /// Building for `forge serve` rather than for publishing
+    preview: bool,
+    plugins: PluginRegistry,
 }
 
 impl PipelineOrchestrator {
@@ -28,9 +50,23 @@ impl PipelineOrchestrator {
             site_dir,
             config,
             force,
+            preview: false,
+            plugins: PluginRegistry::new(),
         }
     }
 
+    /// Build for the dev server. Moved permalinks are not recorded, and the
+    /// URLs of the last published build are kept so that the next
+    /// `forge build` still notices a move.
+    pub fn preview(mut self) -> Self {
+        self.preview = true;
+        self
+    }
+
+    pub fn register_plugin(&mut self, plugin: Box<dyn Plugin>) {
+        self.plugins.register(plugin);
+    }
+
     pub fn run(&self) -> ForgeResult<()> {
         let total_start = Instant::now();
 
@@ -38,47 +74,96 @@ impl PipelineOrchestrator {
         let mut cache = IncrementalCache::load(&self.site_dir, self.force)?;
 
         // Compute config hash
-        let config_str = toml::to_string(&self.config).unwrap_or_default();
-        let config_hash = blake3::hash(config_str.as_bytes()).to_hex().to_string();
+        let config_hash = hash_value(&self.config);
+
+        // Build the template dependency graph so that template edits only
+        // invalidate the outputs that use them
+        let templates = TemplateGraph::load(&self.site_dir, &self.config.theme);
 
-        // Compute template hash
-        let template_hash = self.hash_templates();
+        let full_rebuild = cache.config_changed(&config_hash);
+        if full_rebuild {
+            tracing::info!("Config changed, rebuilding everything");
+        }
 
-        let _full_rebuild =
-            cache.config_changed(&config_hash) || cache.templates_changed(&template_hash);
+        // The template engine is needed from the start: shortcodes in
+        // content are rendered through it
+        let mut tera =
+            engine::create_tera_engine(&self.site_dir, &self.config.theme, self.config.timezone)?;
+
+        // Load translations
+        let translator = Translator::new(&self.site_dir, &self.config.language);
+        let translations = translator.load_all();
+
+        // Templates and markdown share one image processor, which collects
+        // the processed images to write
+        let images = Arc::new(ImageProcessor::new(&self.site_dir, &self.config));
+        engine::register_functions(
+            &mut tera,
+            self.config.base_url.clone(),
+            translations,
+            self.config.language.clone(),
+            Arc::clone(&images),
+        );
 
         // ── Phase 1: LOAD ──
         let load_start = Instant::now();
         tracing::info!("Phase 1: Loading content...");
-        let loader = ContentLoader::new(&self.config);
+        let git_history = if self.config.build.git_dates {
+            GitHistory::load(&self.site_dir)
+        } else {
+            None
+        };
+        let highlighter = Highlighter::load(
+            &self.site_dir,
+            &self.config.build.syntax_theme,
+            self.config.build.syntax_style,
+        )?;
+        let processors = CodeProcessors::new(&self.site_dir, &self.config);
+        let mut loader = ContentLoader::new(
+            &self.config,
+            &highlighter,
+            &images,
+            &processors,
+            git_history,
+            &tera,
+        );
+        for transformer in self.plugins.markdown_transformers() {
+            loader.add_markdown_transformer(transformer);
+        }
         let loaded = loader.load(&self.site_dir)?;
         let load_time = load_start.elapsed();
 
         // ── Phase 2: PARSE (already done during load with parallel potential) ──
         let parse_start = Instant::now();
         tracing::info!(
-            "Phase 2: Processing {} posts, {} pages...",
+            "Phase 2: Processing {} posts, {} pages, {} sections...",
             loaded.posts.len(),
-            loaded.pages.len()
+            loaded.pages.len(),
+            loaded.sections.len()
         );
         let mut posts = loaded.posts;
-        let pages = loaded.pages;
+        let mut pages = loaded.pages;
+        let mut sections = loaded.sections;
+        let withheld = loaded.withheld;
         let parse_time = parse_start.elapsed();
 
         // ── Phase 3: ANALYZE ──
         let analyze_start = Instant::now();
         tracing::info!("Phase 3: Analyzing content...");
 
+        // Resolve links between content now that every permalink is known
+        links::resolve_site_links(
+            &self.site_dir.join("content"),
+            &mut posts,
+            &mut pages,
+            &mut sections,
+        )?;
+        let link_graph = links::build_link_graph(&mut posts, &mut pages, &mut sections);
+
         // Set prev/next navigation
-        for i in 0..posts.len() {
-            if i > 0 {
-                let later_ref = PostRef::from(&posts[i - 1]);
-                posts[i].later = Some(later_ref);
-            }
-            if i + 1 < posts.len() {
-                let earlier_ref = PostRef::from(&posts[i + 1]);
-                posts[i].earlier = Some(earlier_ref);
-            }
+        link_neighbours(&mut posts);
+        for section in &mut sections {
+            link_neighbours(&mut section.posts);
         }
 
         // Build taxonomies
@@ -91,9 +176,58 @@ impl PipelineOrchestrator {
         let site = Site {
             posts,
             pages,
+            sections,
             taxonomies,
             index_paginator,
         };
+        let section_refs: Vec<SectionRef> = site.sections.iter().map(SectionRef::from).collect();
+        let taxonomy_refs: BTreeMap<String, TaxonomyRef> = site
+            .taxonomies
+            .iter()
+            .map(|(name, taxonomy)| (name.clone(), TaxonomyRef::from(taxonomy)))
+            .collect();
+
+        self.check_permalink_collisions(
+            site.all_posts()
+                .map(|p| (p.permalink.as_str(), p.source_path.as_str()))
+                .chain(
+                    site.pages
+                        .iter()
+                        .map(|p| (p.permalink.as_str(), p.source_path.as_str())),
+                ),
+        )?;
+
+        // Remember where moved sources used to live so their old URLs keep working
+        let mut redirect_store = RedirectStore::load(&self.site_dir)?;
+        if self.config.build.auto_redirects && !self.preview {
+            self.record_moved_permalinks(&site, &cache, &mut redirect_store);
+        }
+        let redirects = self.collect_redirects(&site, &mut redirect_store)?;
+
+        // Every template context carries the taxonomy terms and section
+        // summaries, so any change to them invalidates every rendered page.
+        // Only the terms and their counts are hashed, not the posts of each
+        // term, so that editing a post does not render every page again;
+        // listings of a term's or section's posts are tracked on their own.
+        let site_hash = hash_value(&(&taxonomy_refs, &section_refs));
+        let rerender_all = full_rebuild || cache.site_changed(&site_hash);
+
+        // Aggregate hashes for outputs that list many posts or pages
+        let posts_hash = hash_value(
+            &site
+                .posts
+                .iter()
+                .map(|p| (&p.content_hash, post_deps_hash(p)))
+                .collect::<Vec<_>>(),
+        );
+        let pages_hash = hash_value(
+            &site
+                .pages
+                .iter()
+                .map(|p| &p.content_hash)
+                .collect::<Vec<_>>(),
+        );
+        let section_hashes: Vec<String> = site.sections.iter().map(section_hash).collect();
 
         let analyze_time = analyze_start.elapsed();
 
@@ -101,52 +235,69 @@ impl PipelineOrchestrator {
         let render_start = Instant::now();
         tracing::info!("Phase 4: Rendering templates...");
 
-        let mut tera = engine::create_tera_engine(&self.site_dir, &self.config.theme)?;
-
-        // Load translations
-        let translator = Translator::new(&self.site_dir, &self.config.language);
-        let translations = translator.load_all();
-
-        engine::register_functions(
-            &mut tera,
-            self.config.base_url.clone(),
-            translations,
-            self.config.language.clone(),
-        );
-
         let output_dir = self.site_dir.join(&self.config.build.output_dir);
         fs::create_dir_all(&output_dir)?;
 
+        let mut outputs = OutputTracker::new(&output_dir, rerender_all);
+
         // Render index pages (pagination)
         let all_post_refs: Vec<PostRef> = site.posts.iter().map(PostRef::from).collect();
         let index_paginators =
             Paginator::paginate_all(&all_post_refs, self.config.build.posts_per_page, "");
 
         for paginator in &index_paginators {
-            let ctx = context::build_index_context(
-                &site.posts,
-                paginator,
-                &self.config,
-                &site.taxonomies,
-            );
-            let html = tera.render("index.html", &ctx)?;
             let path = if paginator.current_page == 1 {
                 String::new()
             } else {
                 format!("page/{}", paginator.current_page)
             };
-            writer::write_page(&output_dir, &path, &html)?;
+            let hash = hash_value(&(&posts_hash, paginator, templates.chain_hash("index.html")));
+            if outputs.check(&cache, writer::page_file(&path), hash) {
+                let ctx = context::build_index_context(
+                    &site.posts,
+                    paginator,
+                    &self.config,
+                    &site.taxonomies,
+                    &section_refs,
+                );
+                let html = tera.render("index.html", &ctx)?;
+                writer::write_page(&output_dir, &path, &html)?;
+            }
         }
 
+        // Only re-render posts and section entries whose source or
+        // neighbours changed
+        let dirty_posts: Vec<&Post> = site
+            .all_posts()
+            .filter(|post| {
+                rerender_all
+                    || cache.is_dirty(&post.source_path, &post.content_hash)
+                    || cache.deps_changed(&post.source_path, &post_deps_hash(post))
+                    || cache.templates_changed(&post.source_path, &templates)
+                    || !output_dir.join(self.post_output(post)).exists()
+            })
+            .collect();
+
         // Render posts in parallel
-        let post_results: Vec<ForgeResult<()>> = site
-            .posts
+        let post_results: Vec<ForgeResult<()>> = dirty_posts
             .par_iter()
             .map(|post| {
-                let ctx = context::build_post_context(post, &self.config, &site.taxonomies);
-                let template = post.template.as_deref().unwrap_or("post.html");
-                let html = tera.render(template, &ctx)?;
-                writer::write_page(&output_dir, &format!("posts/{}", post.slug), &html)?;
+                let ctx = context::build_post_context(
+                    post,
+                    &self.config,
+                    &site.taxonomies,
+                    &section_refs,
+                );
+                let html = tera.render(post_template(post), &ctx)?;
+                let post_path = permalink::url_path(&self.config.base_url, &post.permalink);
+                writer::write_page(&output_dir, post_path, &html)?;
+                if let Some(bundle_dir) = &post.bundle_dir {
+                    assets::copy_bundle_assets(
+                        Path::new(bundle_dir),
+                        &post.assets,
+                        &output_dir.join(post_path.trim_matches('/')),
+                    )?;
+                }
                 Ok(())
             })
             .collect();
@@ -155,15 +306,31 @@ impl PipelineOrchestrator {
             result?;
         }
 
-        // Render pages in parallel
-        let page_results: Vec<ForgeResult<()>> = site
+        let dirty_pages: Vec<&Page> = site
             .pages
+            .iter()
+            .filter(|page| {
+                rerender_all
+                    || cache.is_dirty(&page.source_path, &page.content_hash)
+                    || cache.deps_changed(&page.source_path, &page_deps_hash(page))
+                    || cache.templates_changed(&page.source_path, &templates)
+                    || !output_dir.join(self.page_output(page)).exists()
+            })
+            .collect();
+
+        // Render pages in parallel
+        let page_results: Vec<ForgeResult<()>> = dirty_pages
             .par_iter()
             .map(|page| {
-                let ctx = context::build_page_context(page, &self.config, &site.taxonomies);
-                let template = page.template.as_deref().unwrap_or("page.html");
-                let html = tera.render(template, &ctx)?;
-                writer::write_page(&output_dir, &page.slug, &html)?;
+                let ctx = context::build_page_context(
+                    page,
+                    &self.config,
+                    &site.taxonomies,
+                    &section_refs,
+                );
+                let html = tera.render(page_template(page), &ctx)?;
+                let page_path = permalink::url_path(&self.config.base_url, &page.permalink);
+                writer::write_page(&output_dir, page_path, &html)?;
                 Ok(())
             })
             .collect();
@@ -172,51 +339,95 @@ impl PipelineOrchestrator {
             result?;
         }
 
+        // Render section listings (pagination)
+        for (section, section_hash) in site.sections.iter().zip(&section_hashes) {
+            let refs: Vec<PostRef> = section.posts.iter().map(PostRef::from).collect();
+            let base_path = format!("/{}", section.slug);
+            let template_hash = templates.chain_hash(&section.template);
+
+            for paginator in Paginator::paginate_all(&refs, section.paginate_by, &base_path) {
+                let path = if paginator.current_page == 1 {
+                    section.slug.clone()
+                } else {
+                    format!("{}/page/{}", section.slug, paginator.current_page)
+                };
+                let hash = hash_value(&(section_hash, &paginator, &template_hash));
+                if outputs.check(&cache, writer::page_file(&path), hash) {
+                    let ctx = context::build_section_context(
+                        section,
+                        &paginator,
+                        &self.config,
+                        &site.taxonomies,
+                        &section_refs,
+                    );
+                    let html = tera.render(&section.template, &ctx)?;
+                    writer::write_page(&output_dir, &path, &html)?;
+                }
+            }
+        }
+
         // Render archive page
-        let archive_ctx =
-            context::build_archive_context(&site.posts, &self.config, &site.taxonomies);
-        if let Ok(html) = tera.render("archive.html", &archive_ctx) {
-            writer::write_page(&output_dir, "archive", &html)?;
+        let archive_hash = hash_value(&(&posts_hash, templates.chain_hash("archive.html")));
+        if outputs.check(&cache, writer::page_file("archive"), archive_hash) {
+            let archive_ctx = context::build_archive_context(
+                &site.posts,
+                &self.config,
+                &site.taxonomies,
+                &section_refs,
+            );
+            if let Ok(html) = tera.render("archive.html", &archive_ctx) {
+                writer::write_page(&output_dir, "archive", &html)?;
+            }
         }
 
         // Render taxonomy pages
+        let taxonomy_template_hash = templates.chain_hash("taxonomy.html");
+        let term_template_hash = templates.chain_hash("taxonomy_single.html");
         for (tax_name, collection) in &site.taxonomies {
             // Taxonomy listing page
-            let tax_ctx =
-                context::build_taxonomy_list_context(collection, &self.config, &site.taxonomies);
-            if let Ok(html) = tera.render("taxonomy.html", &tax_ctx) {
-                writer::write_page(&output_dir, &collection.slug, &html)?;
+            let taxonomy_hash = hash_value(&(&site_hash, collection, &taxonomy_template_hash));
+            if outputs.check(&cache, writer::page_file(&collection.slug), taxonomy_hash) {
+                let tax_ctx = context::build_taxonomy_list_context(
+                    collection,
+                    &self.config,
+                    &site.taxonomies,
+                    &section_refs,
+                );
+                if let Ok(html) = tera.render("taxonomy.html", &tax_ctx) {
+                    writer::write_page(&output_dir, &collection.slug, &html)?;
+                }
             }
 
             // Individual taxonomy term pages
             for item in &collection.items {
-                let item_paginator = Paginator::new(
-                    &item.posts,
-                    self.config.build.posts_per_page,
-                    1,
-                    &format!("{}/{}", collection.slug, item.slug),
-                );
+                let item_path = format!("{}/{}", collection.slug, item.slug);
+                let term_hash = hash_value(&(&site_hash, item, &term_template_hash));
+                if !outputs.check(&cache, writer::page_file(&item_path), term_hash) {
+                    continue;
+                }
+                let item_paginator =
+                    Paginator::new(&item.posts, self.config.build.posts_per_page, 1, &item_path);
                 let ctx = context::build_taxonomy_single_context(
                     tax_name,
                     item,
                     &item_paginator,
                     &self.config,
                     &site.taxonomies,
+                    &section_refs,
                 );
                 if let Ok(html) = tera.render("taxonomy_single.html", &ctx) {
-                    writer::write_page(
-                        &output_dir,
-                        &format!("{}/{}", collection.slug, item.slug),
-                        &html,
-                    )?;
+                    writer::write_page(&output_dir, &item_path, &html)?;
                 }
             }
         }
 
         // Render 404 page
-        let ctx_404 = context::build_404_context(&self.config, &site.taxonomies);
-        if let Ok(html) = tera.render("404.html", &ctx_404) {
-            writer::write_html(&output_dir, "404.html", &html)?;
+        let hash_404 = hash_value(&(&site_hash, templates.chain_hash("404.html")));
+        if outputs.check(&cache, "404.html".to_string(), hash_404) {
+            let ctx_404 = context::build_404_context(&self.config, &site.taxonomies, &section_refs);
+            if let Ok(html) = tera.render("404.html", &ctx_404) {
+                writer::write_html(&output_dir, "404.html", &html)?;
+            }
         }
 
         let render_time = render_start.elapsed();
@@ -228,47 +439,195 @@ impl PipelineOrchestrator {
         // Copy static assets
         assets::copy_static_assets(&self.site_dir, &self.config.theme, &output_dir)?;
 
+        // Copy processed images. Unused ones are only known when every page
+        // was rendered, since templates can process images too.
+        let images_written = images.write_outputs(&output_dir, rerender_all)?;
+
         // Generate RSS feed
         if self.config.build.generate_feed {
-            let rss_xml = feed::generate_rss(&site.posts, &self.config)?;
-            fs::write(output_dir.join("feed.xml"), &rss_xml)?;
+            if outputs.check(&cache, "feed.xml".to_string(), posts_hash.clone()) {
+                let rss_xml = feed::generate_rss(&site.posts, &self.config)?;
+                fs::write(output_dir.join("feed.xml"), &rss_xml)?;
+            }
+
+            if outputs.check(&cache, "atom.xml".to_string(), posts_hash.clone()) {
+                let atom_xml = feed::generate_atom(&site.posts, &self.config)?;
+                fs::write(output_dir.join("atom.xml"), &atom_xml)?;
+            }
+        }
 
-            let atom_xml = feed::generate_atom(&site.posts, &self.config)?;
-            fs::write(output_dir.join("atom.xml"), &atom_xml)?;
+        // Generate section feeds
+        for (section, section_hash) in site.sections.iter().zip(&section_hashes) {
+            if !section.feed {
+                continue;
+            }
+            let key = format!("{}/feed.xml", section.slug);
+            if outputs.check(&cache, key.clone(), section_hash.clone()) {
+                let rss_xml = feed::generate_rss(&section.posts, &self.config)?;
+                writer::write_html(&output_dir, &key, &rss_xml)?;
+            }
         }
 
         // Generate sitemap
-        if self.config.build.generate_sitemap {
-            let sitemap_xml =
-                sitemap::generate_sitemap(&site.posts, &site.pages, &site.taxonomies, &self.config);
+        let sitemap_hash = hash_value(&(&posts_hash, &pages_hash, &section_hashes, &site_hash));
+        if self.config.build.generate_sitemap
+            && outputs.check(&cache, "sitemap.xml".to_string(), sitemap_hash)
+        {
+            let sitemap_xml = sitemap::generate_sitemap(
+                &site.posts,
+                &site.pages,
+                &site.sections,
+                &site.taxonomies,
+                &self.config,
+            );
             fs::write(output_dir.join("sitemap.xml"), &sitemap_xml)?;
         }
 
         // Generate search index
-        if self.config.build.generate_search_index {
+        if self.config.build.generate_search_index
+            && outputs.check(&cache, "search_index.json".to_string(), posts_hash.clone())
+        {
             let search_json = search_index::generate_search_index(&site.posts);
             fs::write(output_dir.join("search_index.json"), &search_json)?;
         }
 
-        let write_time = write_start.elapsed();
+        // Generate the stylesheet for class-based highlighting
+        if self.config.build.syntax_style == HighlightStyle::Classes {
+            let mut css = highlighter.css(&self.config.build.syntax_theme)?;
+            if let Some(dark) = &self.config.build.syntax_theme_dark {
+                css.push_str("\n@media (prefers-color-scheme: dark) {\n");
+                css.push_str(&highlighter.css(dark)?);
+                css.push_str("}\n");
+            }
+            let key = "css/syntax-theme.css".to_string();
+            if outputs.check(&cache, key.clone(), hash_value(&css)) {
+                writer::write_html(&output_dir, &key, &css)?;
+            }
+        }
 
-        // Update cache
-        for post in &site.posts {
-            cache.update_file(
-                post.source_path.clone(),
-                post.content_hash.clone(),
-                output_dir.join(format!("posts/{}/index.html", post.slug)),
-            );
+        // Generate link graph
+        if self.config.build.generate_link_graph {
+            let graph_json = serde_json::to_string_pretty(&link_graph)?;
+            if outputs.check(
+                &cache,
+                "link_graph.json".to_string(),
+                hash_value(&graph_json),
+            ) {
+                fs::write(output_dir.join("link_graph.json"), &graph_json)?;
+            }
+        }
+
+        // Update cache, removing outputs of sources that moved or disappeared
+        let redirect_files: HashSet<PathBuf> = redirects
+            .iter()
+            .map(|r| output_dir.join(r.output_file()))
+            .collect();
+        let mut live_sources = HashSet::new();
+        let mut source_files = HashSet::new();
+        for post in site.all_posts() {
+            let output_path = output_dir.join(self.post_output(post));
+            let record = FileRecord {
+                content_hash: post.content_hash.clone(),
+                output_path,
+                template_deps: templates.dependencies(post_template(post)),
+                deps_hash: post_deps_hash(post),
+                url_path: self.published_path(
+                    &cache,
+                    is_published(post),
+                    &post.source_path,
+                    &post.permalink,
+                ),
+            };
+            source_files.insert(record.output_path.clone());
+            self.record_file(&mut cache, &post.source_path, record, &redirect_files)?;
+            live_sources.insert(post.source_path.clone());
+
+            // Bundle assets are outputs of the post, so copies of removed
+            // assets are pruned like other outputs
+            let post_path = permalink::url_path(&self.config.base_url, &post.permalink);
+            for asset in &post.assets {
+                let key = format!("{}/{asset}", post_path.trim_matches('/'));
+                outputs.keep(key, post.content_hash.clone());
+            }
+        }
+        for page in &site.pages {
+            let output_path = output_dir.join(self.page_output(page));
+            let record = FileRecord {
+                content_hash: page.content_hash.clone(),
+                output_path,
+                template_deps: templates.dependencies(page_template(page)),
+                deps_hash: page_deps_hash(page),
+                url_path: self.published_path(&cache, true, &page.source_path, &page.permalink),
+            };
+            source_files.insert(record.output_path.clone());
+            self.record_file(&mut cache, &page.source_path, record, &redirect_files)?;
+            live_sources.insert(page.source_path.clone());
+        }
+        for stale in cache.prune_files(&live_sources) {
+            writer::remove_output(&stale)?;
+        }
+
+        // Write redirect stubs for aliases and moved permalinks
+        for redirect in &redirects {
+            let key = redirect.output_file();
+            if outputs.check(&cache, key.clone(), hash_value(&redirect.to)) {
+                writer::write_html(&output_dir, &key, &redirects::redirect_page(redirect))?;
+            }
         }
+
+        let redirects_hash = hash_value(&redirects);
+        if self.config.build.generate_netlify_redirects
+            && outputs.check(&cache, "_redirects".to_string(), redirects_hash.clone())
+        {
+            let netlify = redirects::generate_netlify(&redirects, &self.config.base_url);
+            fs::write(output_dir.join("_redirects"), netlify)?;
+        }
+        if self.config.build.generate_nginx_redirects
+            && outputs.check(&cache, NGINX_REDIRECTS.to_string(), redirects_hash)
+        {
+            let nginx = redirects::generate_nginx_map(&redirects, &self.config.base_url);
+            fs::write(output_dir.join(NGINX_REDIRECTS), nginx)?;
+        }
+
+        redirect_store.save()?;
+
+        let (rendered_outputs, skipped_outputs) = (outputs.rendered, outputs.skipped);
+        for (key, hash) in outputs.records {
+            cache.update_output(key, hash);
+        }
+        for stale in cache.prune_outputs(&outputs.live) {
+            // A redirect stub may have been replaced by a page moving back
+            let stale = output_dir.join(stale);
+            if !source_files.contains(&stale) {
+                writer::remove_output(&stale)?;
+            }
+        }
+
         cache.set_config_hash(config_hash);
-        cache.set_template_hash(template_hash);
+        cache.set_template_hashes(templates.hashes());
+        cache.set_site_hash(site_hash);
         cache.save()?;
 
+        let write_time = write_start.elapsed();
+
         let total_time = total_start.elapsed();
 
         // Print build statistics
         println!("\n  Build complete!");
-        println!("  Posts: {}, Pages: {}", site.posts.len(), site.pages.len());
+        println!(
+            "  Posts: {}, Pages: {}, Sections: {}",
+            site.posts.len(),
+            site.pages.len(),
+            site.sections.len()
+        );
+        println!(
+            "  Rendered: {} posts, {} pages, {} listings ({} unchanged)",
+            dirty_posts.len(),
+            dirty_pages.len(),
+            rendered_outputs,
+            site.all_posts().count() - dirty_posts.len() + site.pages.len() - dirty_pages.len()
+                + skipped_outputs
+        );
         println!(
             "  Taxonomies: {}",
             site.taxonomies
@@ -277,6 +636,11 @@ impl PipelineOrchestrator {
                 .collect::<Vec<_>>()
                 .join(", ")
         );
+        if images_written > 0 {
+            println!("  Images: {images_written} processed");
+        }
+        print_withheld("Scheduled", &withheld.scheduled);
+        print_withheld("Expired", &withheld.expired);
         println!("  Output: {}", output_dir.display());
         println!("\n  Timing:");
         println!("    Load:    {:>8.2?}", load_time);
@@ -289,41 +653,315 @@ impl PipelineOrchestrator {
         Ok(())
     }
 
-    fn hash_templates(&self) -> String {
-        let mut hasher = blake3::Hasher::new();
+    /// Output file of a post, relative to the output directory
+    fn post_output(&self, post: &Post) -> String {
+        writer::page_file(permalink::url_path(&self.config.base_url, &post.permalink))
+    }
+
+    /// Output file of a page, relative to the output directory
+    fn page_output(&self, page: &Page) -> String {
+        writer::page_file(permalink::url_path(&self.config.base_url, &page.permalink))
+    }
 
-        let theme_dir = self
-            .site_dir
-            .join("themes")
-            .join(&self.config.theme)
-            .join("templates");
-        if theme_dir.exists() {
-            Self::hash_directory(&mut hasher, &theme_dir);
+    /// Fail the build if two posts or pages resolve to the same URL
+    fn check_permalink_collisions<'a>(
+        &self,
+        sources: impl Iterator<Item = (&'a str, &'a str)>,
+    ) -> ForgeResult<()> {
+        let mut seen: HashMap<&str, &str> = HashMap::new();
+        for (permalink, source_path) in sources {
+            if let Some(other) = seen.insert(permalink, source_path) {
+                return Err(ForgeError::Build(format!(
+                    "{source_path} and {other} both resolve to {permalink}"
+                )));
+            }
         }
+        Ok(())
+    }
 
-        let site_templates = self.site_dir.join("templates");
-        if site_templates.exists() {
-            Self::hash_directory(&mut hasher, &site_templates);
+    /// Record a redirect for every published source whose URL differs from
+    /// the one it was published at in the previous build.
+    fn record_moved_permalinks(
+        &self,
+        site: &Site,
+        cache: &IncrementalCache,
+        store: &mut RedirectStore,
+    ) {
+        let base_url = &self.config.base_url;
+        let sources = site
+            .all_posts()
+            .filter(|p| is_published(p))
+            .map(|p| (&p.source_path, &p.permalink))
+            .chain(site.pages.iter().map(|p| (&p.source_path, &p.permalink)));
+
+        for (source_path, current) in sources {
+            let Some(previous) = cache.file_record(source_path) else {
+                continue;
+            };
+            let current = permalink::url_path(base_url, current);
+            if previous.url_path.is_empty() || previous.url_path == current {
+                continue;
+            }
+            tracing::info!(
+                "{source_path} moved from {} to {current}, recording a redirect",
+                previous.url_path
+            );
+            store.record(
+                &previous.url_path,
+                current,
+                &self.relative_source(source_path),
+            );
+        }
+    }
+
+    /// URL path a source is published at, remembered to notice when it
+    /// moves. Drafts and future posts have none, and preview builds keep the
+    /// one of the last published build.
+    fn published_path(
+        &self,
+        cache: &IncrementalCache,
+        published: bool,
+        source_path: &str,
+        permalink: &str,
+    ) -> String {
+        if self.preview {
+            cache
+                .file_record(source_path)
+                .map(|record| record.url_path.clone())
+                .unwrap_or_default()
+        } else if published {
+            permalink::url_path(&self.config.base_url, permalink).to_string()
+        } else {
+            String::new()
         }
+    }
 
-        hasher.finalize().to_hex().to_string()
+    /// Source path relative to the site root, as stored in `redirects.toml`
+    fn relative_source(&self, source_path: &str) -> String {
+        Path::new(source_path)
+            .strip_prefix(&self.site_dir)
+            .unwrap_or(Path::new(source_path))
+            .to_string_lossy()
+            .replace('\\', "/")
     }
 
-    fn hash_directory(hasher: &mut blake3::Hasher, dir: &std::path::Path) {
-        if let Ok(entries) = std::fs::read_dir(dir) {
-            let mut paths: Vec<_> = entries.filter_map(|e| e.ok()).collect();
-            paths.sort_by_key(|e| e.path());
-
-            for entry in paths {
-                let path = entry.path();
-                if path.is_file() {
-                    if let Ok(content) = std::fs::read(&path) {
-                        hasher.update(&content);
-                    }
-                } else if path.is_dir() {
-                    Self::hash_directory(hasher, &path);
+    /// Gather the redirects declared through front matter aliases and the
+    /// ones recorded in `redirects.toml`. An alias may not shadow a real page
+    /// or be claimed twice; recorded redirects give way to both.
+    fn collect_redirects(
+        &self,
+        site: &Site,
+        store: &mut RedirectStore,
+    ) -> ForgeResult<Vec<Redirect>> {
+        let base_url = &self.config.base_url;
+        let sources = site
+            .all_posts()
+            .map(|p| (&p.aliases, &p.permalink, &p.source_path))
+            .chain(
+                site.pages
+                    .iter()
+                    .map(|p| (&p.aliases, &p.permalink, &p.source_path)),
+            );
+
+        let taken: HashSet<&str> = site
+            .all_posts()
+            .map(|p| permalink::url_path(base_url, &p.permalink))
+            .chain(
+                site.pages
+                    .iter()
+                    .map(|p| permalink::url_path(base_url, &p.permalink)),
+            )
+            .collect();
+
+        let mut claimed: HashMap<String, &str> = HashMap::new();
+        let mut redirects = Vec::new();
+        for (aliases, target, source_path) in sources {
+            for alias in aliases {
+                let redirect = Redirect::from_alias(alias, target);
+                if taken.contains(redirect.from.as_str()) {
+                    return Err(ForgeError::Build(format!(
+                        "Alias {} in {source_path} points at an existing page",
+                        redirect.from
+                    )));
                 }
+                if let Some(other) = claimed.insert(redirect.from.clone(), source_path) {
+                    return Err(ForgeError::Build(format!(
+                        "Alias {} is declared by both {source_path} and {other}",
+                        redirect.from
+                    )));
+                }
+                redirects.push(redirect);
             }
         }
+
+        // Recorded redirects follow their source to its current permalink
+        let current: HashMap<String, &str> = site
+            .all_posts()
+            .map(|p| (&p.source_path, &p.permalink))
+            .chain(site.pages.iter().map(|p| (&p.source_path, &p.permalink)))
+            .map(|(source, link)| (self.relative_source(source), link.as_str()))
+            .collect();
+
+        let mut revived = Vec::new();
+        for recorded in &store.redirects {
+            if taken.contains(recorded.from.as_str()) {
+                // The old URL is in use again
+                revived.push(recorded.from.clone());
+                continue;
+            }
+            if claimed.contains_key(&recorded.from) {
+                continue;
+            }
+            let to = match current.get(&recorded.source) {
+                Some(link) => link.to_string(),
+                None => permalink::to_url(base_url, &recorded.to),
+            };
+            claimed.insert(recorded.from.clone(), "redirects.toml");
+            redirects.push(Redirect {
+                from: recorded.from.clone(),
+                to,
+            });
+        }
+        for from in revived {
+            store.remove(&from);
+        }
+
+        redirects.sort_by(|a, b| a.from.cmp(&b.from));
+        Ok(redirects)
+    }
+
+    /// Record a rendered source file in the cache, removing its previous
+    /// output if the file now renders somewhere else (unless a redirect stub
+    /// took its place).
+    fn record_file(
+        &self,
+        cache: &mut IncrementalCache,
+        source_path: &str,
+        record: FileRecord,
+        redirect_files: &HashSet<PathBuf>,
+    ) -> ForgeResult<()> {
+        if let Some(previous) = cache.file_record(source_path) {
+            if previous.output_path != record.output_path
+                && !redirect_files.contains(&previous.output_path)
+            {
+                writer::remove_output(&previous.output_path)?;
+            }
+        }
+        cache.update_file(source_path.to_string(), record);
+        Ok(())
+    }
+}
+
+/// Set prev/next navigation between consecutive posts
+fn link_neighbours(posts: &mut [Post]) {
+    for i in 0..posts.len() {
+        if i > 0 {
+            let later_ref = PostRef::from(&posts[i - 1]);
+            posts[i].later = Some(later_ref);
+        }
+        if i + 1 < posts.len() {
+            let earlier_ref = PostRef::from(&posts[i + 1]);
+            posts[i].earlier = Some(earlier_ref);
+        }
+    }
+}
+
+/// Hash of everything a section listing or feed depends on
+fn section_hash(section: &Section) -> String {
+    hash_value(&(
+        &section.content_hash,
+        section
+            .posts
+            .iter()
+            .map(|p| (&p.content_hash, post_deps_hash(p)))
+            .collect::<Vec<_>>(),
+    ))
+}
+
+/// Whether a post is public: neither a draft nor dated in the future
+fn is_published(post: &Post) -> bool {
+    !post.draft && post.date <= chrono::Utc::now()
+}
+
+fn post_template(post: &Post) -> &str {
+    post.template.as_deref().unwrap_or("post.html")
+}
+
+fn page_template(page: &Page) -> &str {
+    page.template.as_deref().unwrap_or("page.html")
+}
+
+/// Hash of the inputs of a post page besides its own source
+fn post_deps_hash(post: &Post) -> String {
+    hash_value(&(&post.earlier, &post.later, &post.backlinks))
+}
+
+/// Hash of the inputs of a page besides its own source
+fn page_deps_hash(page: &Page) -> String {
+    hash_value(&page.backlinks)
+}
+
+/// List content left out of the build, soonest date first
+fn print_withheld(label: &str, items: &[WithheldItem]) {
+    if items.is_empty() {
+        return;
+    }
+    let mut items: Vec<&WithheldItem> = items.iter().collect();
+    items.sort_by_key(|item| item.date);
+
+    println!("  {label}: {}", items.len());
+    for item in items {
+        println!(
+            "    {}  {} ({})",
+            item.date.format("%Y-%m-%d %H:%M"),
+            item.title,
+            item.source_path
+        );
+    }
+}
+
+/// Decides which generated outputs need rendering and collects their
+/// input hashes for the manifest.
+struct OutputTracker<'a> {
+    output_dir: &'a Path,
+    rerender_all: bool,
+    records: Vec<(String, String)>,
+    live: HashSet<String>,
+    rendered: usize,
+    skipped: usize,
+}
+
+impl<'a> OutputTracker<'a> {
+    fn new(output_dir: &'a Path, rerender_all: bool) -> Self {
+        Self {
+            output_dir,
+            rerender_all,
+            records: Vec::new(),
+            live: HashSet::new(),
+            rendered: 0,
+            skipped: 0,
+        }
+    }
+
+    /// Record an output that was written along with another one, such as a
+    /// bundle asset, so that it is pruned once no build produces it
+    fn keep(&mut self, key: String, hash: String) {
+        self.live.insert(key.clone());
+        self.records.push((key, hash));
+    }
+
+    /// Returns true if the output at `key` must be (re)written.
+    fn check(&mut self, cache: &IncrementalCache, key: String, hash: String) -> bool {
+        let dirty = self.rerender_all
+            || cache.output_changed(&key, &hash)
+            || !self.output_dir.join(&key).exists();
+        if dirty {
+            self.rendered += 1;
+        } else {
+            self.skipped += 1;
+        }
+        self.live.insert(key.clone());
+        self.records.push((key, hash));
+        dirty
     }
 }
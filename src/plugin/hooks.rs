@@ -1,4 +1,5 @@
 use crate::content::post::Post;
+use crate::content::transform::EventTransformer;
 use crate::error::ForgeResult;
 
 /// Plugin lifecycle hooks
@@ -11,6 +12,11 @@ pub trait Plugin: Send + Sync {
         100
     }
 
+    /// Transformers to add to the markdown renderer
+    fn markdown_transformers(&self) -> Vec<Box<dyn EventTransformer>> {
+        Vec::new()
+    }
+
     /// Called after content is loaded, before rendering
     fn on_content_loaded(&self, _posts: &mut Vec<Post>) -> ForgeResult<()> {
         Ok(())
@@ -1,4 +1,5 @@
 use crate::content::post::Post;
+use crate::content::transform::EventTransformer;
 use crate::error::ForgeResult;
 use crate::plugin::hooks::Plugin;
 
@@ -18,6 +19,13 @@ impl PluginRegistry {
         self.plugins.sort_by_key(|p| p.priority());
     }
 
+    pub fn markdown_transformers(&self) -> Vec<Box<dyn EventTransformer>> {
+        self.plugins
+            .iter()
+            .flat_map(|plugin| plugin.markdown_transformers())
+            .collect()
+    }
+
     pub fn on_content_loaded(&self, posts: &mut Vec<Post>) -> ForgeResult<()> {
         for plugin in &self.plugins {
             plugin.on_content_loaded(posts)?;
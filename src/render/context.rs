@@ -4,6 +4,7 @@ use tera::Context;
 use crate::config::SiteConfig;
 use crate::content::page::Page;
 use crate::content::post::Post;
+use crate::content::section::{Section, SectionRef};
 use crate::render::pagination::Paginator;
 use crate::taxonomy::TaxonomyCollection;
 
@@ -11,8 +12,9 @@ pub fn build_post_context(
     post: &Post,
     config: &SiteConfig,
     taxonomies: &HashMap<String, TaxonomyCollection>,
+    sections: &[SectionRef],
 ) -> Context {
-    let mut ctx = base_context(config, taxonomies);
+    let mut ctx = base_context(config, taxonomies, sections);
     ctx.insert("post", post);
     ctx.insert("page_title", &post.title);
     ctx
@@ -22,8 +24,9 @@ pub fn build_page_context(
     page: &Page,
     config: &SiteConfig,
     taxonomies: &HashMap<String, TaxonomyCollection>,
+    sections: &[SectionRef],
 ) -> Context {
-    let mut ctx = base_context(config, taxonomies);
+    let mut ctx = base_context(config, taxonomies, sections);
     ctx.insert("page", page);
     ctx.insert("page_title", &page.title);
     ctx
@@ -34,20 +37,36 @@ pub fn build_index_context(
     paginator: &Paginator,
     config: &SiteConfig,
     taxonomies: &HashMap<String, TaxonomyCollection>,
+    sections: &[SectionRef],
 ) -> Context {
-    let mut ctx = base_context(config, taxonomies);
+    let mut ctx = base_context(config, taxonomies, sections);
     ctx.insert("posts", posts);
     ctx.insert("paginator", paginator);
     ctx.insert("page_title", &config.title);
     ctx
 }
 
+pub fn build_section_context(
+    section: &Section,
+    paginator: &Paginator,
+    config: &SiteConfig,
+    taxonomies: &HashMap<String, TaxonomyCollection>,
+    sections: &[SectionRef],
+) -> Context {
+    let mut ctx = base_context(config, taxonomies, sections);
+    ctx.insert("section", section);
+    ctx.insert("paginator", paginator);
+    ctx.insert("page_title", &section.title);
+    ctx
+}
+
 pub fn build_taxonomy_list_context(
     taxonomy: &TaxonomyCollection,
     config: &SiteConfig,
     taxonomies: &HashMap<String, TaxonomyCollection>,
+    sections: &[SectionRef],
 ) -> Context {
-    let mut ctx = base_context(config, taxonomies);
+    let mut ctx = base_context(config, taxonomies, sections);
     ctx.insert("taxonomy", taxonomy);
     ctx.insert("page_title", &taxonomy.name);
     ctx
@@ -59,8 +78,9 @@ pub fn build_taxonomy_single_context(
     paginator: &Paginator,
     config: &SiteConfig,
     taxonomies: &HashMap<String, TaxonomyCollection>,
+    sections: &[SectionRef],
 ) -> Context {
-    let mut ctx = base_context(config, taxonomies);
+    let mut ctx = base_context(config, taxonomies, sections);
     ctx.insert("taxonomy_name", taxonomy_name);
     ctx.insert("term", item);
     ctx.insert("paginator", paginator);
@@ -72,8 +92,9 @@ pub fn build_archive_context(
     posts: &[Post],
     config: &SiteConfig,
     taxonomies: &HashMap<String, TaxonomyCollection>,
+    sections: &[SectionRef],
 ) -> Context {
-    let mut ctx = base_context(config, taxonomies);
+    let mut ctx = base_context(config, taxonomies, sections);
     ctx.insert("posts", posts);
     ctx.insert("page_title", "Archive");
     ctx
@@ -82,13 +103,18 @@ pub fn build_archive_context(
 pub fn build_404_context(
     config: &SiteConfig,
     taxonomies: &HashMap<String, TaxonomyCollection>,
+    sections: &[SectionRef],
 ) -> Context {
-    let mut ctx = base_context(config, taxonomies);
+    let mut ctx = base_context(config, taxonomies, sections);
     ctx.insert("page_title", "Page Not Found");
     ctx
 }
 
-fn base_context(config: &SiteConfig, taxonomies: &HashMap<String, TaxonomyCollection>) -> Context {
+fn base_context(
+    config: &SiteConfig,
+    taxonomies: &HashMap<String, TaxonomyCollection>,
+    sections: &[SectionRef],
+) -> Context {
     let mut ctx = Context::new();
     ctx.insert("config", config);
     ctx.insert("site_title", &config.title);
@@ -97,6 +123,7 @@ fn base_context(config: &SiteConfig, taxonomies: &HashMap<String, TaxonomyCollec
     ctx.insert("author", &config.author);
     ctx.insert("description", &config.description);
     ctx.insert("taxonomies", taxonomies);
+    ctx.insert("sections", sections);
     ctx.insert("extra", &config.extra);
     ctx
 }
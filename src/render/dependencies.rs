@@ -0,0 +1,156 @@
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+use walkdir::WalkDir;
+
+/// A template known to the engine, with the templates it references directly.
+#[derive(Debug, Clone)]
+struct TemplateNode {
+    hash: String,
+    deps: Vec<String>,
+}
+
+/// Dependency graph of the theme and site templates, built from their
+/// `extends`, `include` and `import` tags.
+#[derive(Debug, Default)]
+pub struct TemplateGraph {
+    templates: HashMap<String, TemplateNode>,
+}
+
+impl TemplateGraph {
+    /// Scan templates the same way `create_tera_engine` loads them: theme
+    /// templates first, then site-level overrides with the same name.
+    pub fn load(site_dir: &Path, theme: &str) -> Self {
+        let mut graph = Self::default();
+
+        let theme_templates_dir = site_dir.join("themes").join(theme).join("templates");
+        graph.scan_dir(&theme_templates_dir);
+
+        let site_templates_dir = site_dir.join("templates");
+        graph.scan_dir(&site_templates_dir);
+
+        graph
+    }
+
+    fn scan_dir(&mut self, dir: &Path) {
+        if !dir.exists() {
+            return;
+        }
+
+        for entry in WalkDir::new(dir)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().extension().is_some_and(|ext| ext == "html"))
+        {
+            let path = entry.path();
+            let Ok(source) = std::fs::read_to_string(path) else {
+                continue;
+            };
+            let name = path
+                .strip_prefix(dir)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .replace('\\', "/");
+
+            self.templates.insert(
+                name,
+                TemplateNode {
+                    hash: blake3::hash(source.as_bytes()).to_hex().to_string(),
+                    deps: parse_dependencies(&source),
+                },
+            );
+        }
+    }
+
+    /// The template itself plus every template it transitively extends,
+    /// includes or imports, sorted by name.
+    pub fn dependencies(&self, name: &str) -> Vec<String> {
+        let mut seen = BTreeSet::new();
+        let mut stack = vec![name.to_string()];
+
+        while let Some(current) = stack.pop() {
+            if !seen.insert(current.clone()) {
+                continue;
+            }
+            if let Some(node) = self.templates.get(&current) {
+                stack.extend(node.deps.iter().cloned());
+            }
+        }
+
+        seen.into_iter().collect()
+    }
+
+    /// Content hash of a single template, if it exists.
+    pub fn hash(&self, name: &str) -> Option<&str> {
+        self.templates.get(name).map(|node| node.hash.as_str())
+    }
+
+    /// Combined hash of a template and everything it depends on.
+    pub fn chain_hash(&self, name: &str) -> String {
+        let mut hasher = blake3::Hasher::new();
+        for dep in self.dependencies(name) {
+            hasher.update(dep.as_bytes());
+            hasher.update(self.hash(&dep).unwrap_or("missing").as_bytes());
+        }
+        hasher.finalize().to_hex().to_string()
+    }
+
+    /// Content hashes of every known template, keyed by template name.
+    pub fn hashes(&self) -> HashMap<String, String> {
+        self.templates
+            .iter()
+            .map(|(name, node)| (name.clone(), node.hash.clone()))
+            .collect()
+    }
+}
+
+/// Extract the template names referenced by `extends`, `include` and
+/// `import` tags.
+fn parse_dependencies(source: &str) -> Vec<String> {
+    let mut deps = Vec::new();
+    let mut rest = source;
+
+    while let Some(start) = rest.find("{%") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("%}") else {
+            break;
+        };
+        let tag = after[..end].trim_matches('-').trim();
+        rest = &after[end + 2..];
+
+        let (keyword, args) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+        match keyword {
+            "extends" | "import" => {
+                if let Some(name) = quoted_strings(args).into_iter().next() {
+                    deps.push(name);
+                }
+            }
+            // `include` accepts a single name or a list of fallbacks
+            "include" => deps.extend(quoted_strings(args)),
+            // The contents of raw blocks are not template code
+            "raw" => match rest.find("endraw") {
+                Some(pos) => rest = &rest[pos..],
+                None => break,
+            },
+            _ => {}
+        }
+    }
+
+    deps.sort();
+    deps.dedup();
+    deps
+}
+
+/// Collect the contents of all single- or double-quoted strings.
+fn quoted_strings(s: &str) -> Vec<String> {
+    let mut strings = Vec::new();
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '"' || c == '\'' || c == '`' {
+            let value: String = chars.by_ref().take_while(|&ch| ch != c).collect();
+            strings.push(value);
+        }
+    }
+
+    strings
+}
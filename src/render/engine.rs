@@ -1,43 +1,61 @@
+use chrono_tz::Tz;
 use std::collections::HashMap;
-use std::path::Path;
-use tera::{Function, Result as TeraResult, Tera, Value};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tera::{Filter, Function, Result as TeraResult, Tera, Value};
+use walkdir::WalkDir;
 
+use crate::content::frontmatter::FrontMatterDate;
 use crate::error::ForgeResult;
+use crate::images::{ImageProcessor, OutputFormat, ResizeOp, ResizeRequest};
 
-pub fn create_tera_engine(site_dir: &Path, theme: &str) -> ForgeResult<Tera> {
+pub fn create_tera_engine(site_dir: &Path, theme: &str, timezone: Tz) -> ForgeResult<Tera> {
     let mut tera = Tera::default();
 
-    // Load theme templates first
+    // Theme templates first, then site-level overrides with the same name.
+    // Adding them in one batch lets the later file win and lets site
+    // templates extend or include theme templates.
     let theme_templates_dir = site_dir.join("themes").join(theme).join("templates");
-    if theme_templates_dir.exists() {
-        let pattern = format!("{}/**/*.html", theme_templates_dir.display());
-        let theme_tera = Tera::new(&pattern)?;
-        tera.extend(&theme_tera)?;
-    }
-
-    // Load site-level template overrides
     let site_templates_dir = site_dir.join("templates");
-    if site_templates_dir.exists() {
-        let pattern = format!("{}/**/*.html", site_templates_dir.display());
-        let site_tera = Tera::new(&pattern)?;
-        tera.extend(&site_tera)?;
-    }
+    let mut files = template_files(&theme_templates_dir);
+    files.extend(template_files(&site_templates_dir));
+    tera.add_template_files(files)?;
 
     // Disable auto-escaping since we control all template variables
     tera.autoescape_on(vec![]);
 
     // Register custom filters
-    tera.register_filter("date_format", date_format_filter);
+    tera.register_filter("date_format", DateFormatFilter { timezone });
     tera.register_filter("truncate_words", truncate_words_filter);
 
     Ok(tera)
 }
 
+/// `.html` files under a templates directory, named by their path in it
+fn template_files(dir: &Path) -> Vec<(PathBuf, Option<String>)> {
+    WalkDir::new(dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().extension().is_some_and(|ext| ext == "html"))
+        .map(|entry| {
+            let path = entry.path();
+            let name = path
+                .strip_prefix(dir)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .replace('\\', "/");
+            (path.to_path_buf(), Some(name))
+        })
+        .collect()
+}
+
 pub fn register_functions(
     tera: &mut Tera,
     base_url: String,
     translations: HashMap<String, HashMap<String, String>>,
     default_lang: String,
+    images: Arc<ImageProcessor>,
 ) {
     tera.register_function(
         "get_url",
@@ -53,25 +71,31 @@ pub fn register_functions(
             default_lang,
         },
     );
+    tera.register_function("resize_image", ResizeImageFunction { images });
 }
 
-/// Filter: format a date string
-fn date_format_filter(value: &Value, args: &HashMap<String, Value>) -> TeraResult<Value> {
-    let date_str = value
-        .as_str()
-        .ok_or_else(|| tera::Error::msg("date_format: expected string value"))?;
+/// Filter: format a date string in the site timezone
+struct DateFormatFilter {
+    timezone: Tz,
+}
 
-    let format = args
-        .get("format")
-        .and_then(|v| v.as_str())
-        .unwrap_or("%B %d, %Y");
+impl Filter for DateFormatFilter {
+    fn filter(&self, value: &Value, args: &HashMap<String, Value>) -> TeraResult<Value> {
+        let date_str = value
+            .as_str()
+            .ok_or_else(|| tera::Error::msg("date_format: expected string value"))?;
 
-    let date = chrono::DateTime::parse_from_rfc3339(date_str)
-        .or_else(|_| chrono::DateTime::parse_from_str(date_str, "%Y-%m-%dT%H:%M:%S%.fZ"))
-        .or_else(|_| chrono::DateTime::parse_from_str(date_str, "%Y-%m-%dT%H:%M:%SZ"))
-        .map_err(|e| tera::Error::msg(format!("date_format: {e}")))?;
+        let format = args
+            .get("format")
+            .and_then(|v| v.as_str())
+            .unwrap_or("%B %d, %Y");
 
-    Ok(Value::String(date.format(format).to_string()))
+        let date = FrontMatterDate::parse(date_str)
+            .ok_or_else(|| tera::Error::msg(format!("date_format: invalid date '{date_str}'")))?
+            .in_timezone(&self.timezone);
+
+        Ok(Value::String(date.format(format).to_string()))
+    }
 }
 
 /// Filter: truncate text to N words
@@ -186,3 +210,81 @@ impl Function for TransFunction {
         true
     }
 }
+
+/// Resize an image from `static/` or `content/`:
+/// `resize_image(path, width, height, op, format, quality)`
+struct ResizeImageFunction {
+    images: Arc<ImageProcessor>,
+}
+
+impl Function for ResizeImageFunction {
+    fn call(&self, args: &HashMap<String, Value>) -> TeraResult<Value> {
+        let path = args
+            .get("path")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| tera::Error::msg("resize_image: missing 'path' argument"))?;
+        let dimension = |name: &str| -> TeraResult<Option<u32>> {
+            match args.get(name) {
+                None => Ok(None),
+                Some(value) => value
+                    .as_u64()
+                    .filter(|&v| v > 0 && v <= u32::MAX as u64)
+                    .map(|v| Some(v as u32))
+                    .ok_or_else(|| {
+                        tera::Error::msg(format!(
+                            "resize_image: '{name}' must be a positive integer"
+                        ))
+                    }),
+            }
+        };
+        let width = dimension("width")?;
+        let height = dimension("height")?;
+
+        let default_op = match (width, height) {
+            (Some(_), None) => "fit_width",
+            (None, Some(_)) => "fit_height",
+            _ => "fill",
+        };
+        let op_name = args
+            .get("op")
+            .and_then(|v| v.as_str())
+            .unwrap_or(default_op);
+        let op = ResizeOp::parse(op_name).ok_or_else(|| {
+            tera::Error::msg(format!(
+                "resize_image: unknown op '{op_name}', expected scale, fit_width, fit_height, fit or fill"
+            ))
+        })?;
+        let format_name = args
+            .get("format")
+            .and_then(|v| v.as_str())
+            .unwrap_or("auto");
+        let format = OutputFormat::parse(format_name).ok_or_else(|| {
+            tera::Error::msg(format!(
+                "resize_image: unknown format '{format_name}', expected auto, jpg, png or webp"
+            ))
+        })?;
+        let quality = match args.get("quality").and_then(|v| v.as_u64()) {
+            Some(quality @ 1..=100) => quality as u8,
+            Some(_) => return Err(tera::Error::msg("resize_image: 'quality' must be 1-100")),
+            None => self.images.default_quality(),
+        };
+
+        let source = self
+            .images
+            .find(path)
+            .ok_or_else(|| tera::Error::msg(format!("resize_image: image '{path}' not found")))?;
+        let request = ResizeRequest {
+            op,
+            width,
+            height,
+            format,
+            quality,
+        };
+        let image = self
+            .images
+            .resize(&source, &request)
+            .map_err(|e| tera::Error::msg(format!("resize_image: {path}: {e}")))?;
+
+        Ok(tera::to_value(image)?)
+    }
+}
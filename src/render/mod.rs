@@ -1,3 +1,4 @@
 pub mod context;
+pub mod dependencies;
 pub mod engine;
 pub mod pagination;
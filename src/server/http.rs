@@ -8,6 +8,7 @@ use tower_http::services::ServeDir;
 use tower_livereload::LiveReloadLayer;
 
 use crate::config::SiteConfig;
+use crate::content::highlight::SYNTAXES_DIR;
 use crate::error::ForgeResult;
 use crate::pipeline::orchestrator::PipelineOrchestrator;
 
@@ -18,7 +19,7 @@ pub async fn start_server(
     _open: bool,
 ) -> ForgeResult<()> {
     // Do an initial build
-    let orchestrator = PipelineOrchestrator::new(site_dir.clone(), config.clone(), false);
+    let orchestrator = PipelineOrchestrator::new(site_dir.clone(), config.clone(), false).preview();
     orchestrator.run()?;
 
     let output_dir = site_dir.join(&config.build.output_dir);
@@ -59,6 +60,7 @@ pub async fn start_server(
             watch_site_dir.join("templates"),
             watch_site_dir.join("static"),
             watch_site_dir.join("themes"),
+            watch_site_dir.join(SYNTAXES_DIR),
         ];
 
         for dir in &dirs_to_watch {
@@ -103,7 +105,8 @@ pub async fn start_server(
                             rebuild_site_dir.clone(),
                             current_config,
                             false,
-                        );
+                        )
+                        .preview();
                         match orchestrator.run() {
                             Ok(_) => {
                                 reloader.reload();
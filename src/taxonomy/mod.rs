@@ -19,3 +19,40 @@ pub struct TaxonomyItem {
     pub posts: Vec<PostRef>,
     pub permalink: String,
 }
+
+/// The part of a taxonomy that every rendered page is hashed with: its
+/// terms with their post counts, but not their posts, so that editing a
+/// post does not render every page again
+#[derive(Debug, Clone, Serialize)]
+pub struct TaxonomyRef {
+    pub name: String,
+    pub slug: String,
+    pub items: Vec<TermRef>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TermRef {
+    pub name: String,
+    pub slug: String,
+    pub post_count: usize,
+    pub permalink: String,
+}
+
+impl From<&TaxonomyCollection> for TaxonomyRef {
+    fn from(taxonomy: &TaxonomyCollection) -> Self {
+        Self {
+            name: taxonomy.name.clone(),
+            slug: taxonomy.slug.clone(),
+            items: taxonomy
+                .items
+                .iter()
+                .map(|item| TermRef {
+                    name: item.name.clone(),
+                    slug: item.slug.clone(),
+                    post_count: item.post_count,
+                    permalink: item.permalink.clone(),
+                })
+                .collect(),
+        }
+    }
+}
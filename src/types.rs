@@ -5,6 +5,7 @@ use std::path::PathBuf;
 
 use crate::content::page::Page;
 use crate::content::post::Post;
+use crate::content::section::Section;
 use crate::render::pagination::Paginator;
 use crate::taxonomy::TaxonomyCollection;
 
@@ -26,30 +27,68 @@ impl From<&Post> for PostRef {
     }
 }
 
+/// A post or page that links to the current one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Backlink {
+    #[serde(flatten)]
+    pub source: PostRef,
+    /// Text around the link in the linking content, HTML-escaped
+    pub context: String,
+}
+
 /// The fully assembled site data used during rendering
 #[derive(Debug)]
 pub struct Site {
     pub posts: Vec<Post>,
     pub pages: Vec<Page>,
+    pub sections: Vec<Section>,
     pub taxonomies: HashMap<String, TaxonomyCollection>,
     pub index_paginator: Paginator,
 }
 
+impl Site {
+    /// Regular posts followed by the entries of every section
+    pub fn all_posts(&self) -> impl Iterator<Item = &Post> {
+        self.posts
+            .iter()
+            .chain(self.sections.iter().flat_map(|s| &s.posts))
+    }
+}
+
 /// Build manifest for incremental builds
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BuildManifest {
     pub version: String,
     pub last_build: DateTime<Utc>,
     pub config_hash: String,
-    pub template_hash: String,
+    /// Content hash of every template, keyed by template name
+    #[serde(default)]
+    pub template_hashes: HashMap<String, String>,
+    /// Hash of the context shared by every rendered page (taxonomies)
+    #[serde(default)]
+    pub site_hash: String,
     pub file_hashes: HashMap<String, FileRecord>,
+    /// Input hashes of generated outputs that are not tied to a single
+    /// source file (listings, taxonomy pages, feeds), keyed by output path
+    #[serde(default)]
+    pub outputs: HashMap<String, String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileRecord {
     pub content_hash: String,
     pub output_path: PathBuf,
+    /// Templates used to render this file, including everything they
+    /// extend, include or import
     pub template_deps: Vec<String>,
+    /// Hash of the inputs a rendered file depends on besides its own
+    /// source (e.g. prev/next navigation)
+    #[serde(default)]
+    pub deps_hash: String,
+    /// URL path the file was published at, used to detect moved permalinks.
+    /// Empty for drafts and future posts.
+    #[serde(default)]
+    pub url_path: String,
 }
 
 impl BuildManifest {
@@ -58,8 +97,10 @@ impl BuildManifest {
             version: env!("CARGO_PKG_VERSION").to_string(),
             last_build: Utc::now(),
             config_hash: String::new(),
-            template_hash: String::new(),
+            template_hashes: HashMap::new(),
+            site_hash: String::new(),
             file_hashes: HashMap::new(),
+            outputs: HashMap::new(),
         }
     }
 }
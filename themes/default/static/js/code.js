@@ -0,0 +1,28 @@
+(function() {
+    'use strict';
+
+    if (!navigator.clipboard) return;
+
+    // Copy buttons of annotated code blocks stay hidden without this script
+    var blocks = document.querySelectorAll('.code-block');
+    Array.prototype.forEach.call(blocks, function(block) {
+        var button = block.querySelector('.code-copy');
+        var lines = block.querySelectorAll('.code-line');
+        if (!button) return;
+
+        button.hidden = false;
+        button.addEventListener('click', function() {
+            var text = Array.prototype.map.call(lines, function(line) {
+                var copy = line.cloneNode(true);
+                var number = copy.querySelector('.code-lineno');
+                if (number) copy.removeChild(number);
+                return copy.textContent;
+            }).join('\n');
+
+            navigator.clipboard.writeText(text).then(function() {
+                button.textContent = 'Copied';
+                setTimeout(function() { button.textContent = 'Copy'; }, 2000);
+            });
+        });
+    });
+})();